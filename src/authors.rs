@@ -0,0 +1,95 @@
+//! Commit authors of the repository, to add as users of the project.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::PiqoError;
+use crate::models::Project;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+}
+
+/// Authors of the commits reachable from `HEAD` of the repository at `dir`, most commits
+/// first.
+///
+/// Names and emails go through the repository's mailmap. Authors are told apart by email
+/// ignoring case and named by the name they used most. `since` is a unix timestamp only
+/// counting commits after it. A repository without commits has no authors.
+pub fn authors(dir: &Path, since: Option<i64>) -> Result<Vec<Author>, PiqoError> {
+    let mut log = Command::new("git");
+    log.arg("-C")
+        .arg(dir)
+        .args(["log", "--use-mailmap", "--format=%aN%x1f%aE"]);
+    if let Some(since) = since {
+        log.arg(format!("--since=@{}", since));
+    }
+    let output = log.output()?;
+    if !output.status.success() {
+        return Ok(vec![]);
+    }
+
+    // Per email: the names used with their commit counts
+    let mut by_email: Vec<(String, Vec<(String, usize)>)> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((name, email)) = line.split_once('\x1f') else {
+            continue;
+        };
+        let (name, email) = (name.trim(), email.trim());
+        if email.is_empty() {
+            continue;
+        }
+        let names = match by_email
+            .iter_mut()
+            .find(|(e, _)| e.eq_ignore_ascii_case(email))
+        {
+            Some((_, names)) => names,
+            None => {
+                by_email.push((email.to_string(), vec![]));
+                &mut by_email.last_mut().unwrap().1
+            }
+        };
+        match names.iter_mut().find(|(n, _)| n == name) {
+            Some((_, count)) => *count += 1,
+            None => names.push((name.to_string(), 1)),
+        }
+    }
+
+    let mut authors = by_email
+        .into_iter()
+        .map(|(email, names)| {
+            let commits = names.iter().map(|(_, count)| count).sum();
+            // The first name wins ties, git log lists the newest commits first
+            let name = names
+                .iter()
+                .rev()
+                .max_by_key(|(_, count)| *count)
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default();
+            Author {
+                name,
+                email,
+                commits,
+            }
+        })
+        .collect::<Vec<Author>>();
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits).then(a.name.cmp(&b.name)));
+    Ok(authors)
+}
+
+/// Authors who aren't users of the project yet, going by their email ignoring case.
+pub fn new_authors(p: &Project, authors: Vec<Author>) -> Vec<Author> {
+    authors
+        .into_iter()
+        .filter(|a| {
+            !p.users.iter().any(|u| {
+                u.git_email
+                    .as_deref()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(&a.email))
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,127 @@
+//! Branches named after tasks, like `piqo/4821-fix-login`.
+//!
+//! Branch names follow a template set with `git config piqo.branchTemplate`, where `{id}`
+//! stands for the task id and `{slug}` for the task name made fit for a branch name.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::data_storage;
+use crate::error::PiqoError;
+use crate::models::{Project, Task};
+
+/// Template used when `piqo.branchTemplate` isn't set.
+pub const DEFAULT_BRANCH_TEMPLATE: &str = "piqo/{id}-{slug}";
+
+/// Longest slug of the task name put in a branch name.
+const MAX_SLUG_LEN: usize = 40;
+
+/// The branch template of the repository containing `dir`.
+///
+/// Fails for templates without `{id}` or with `{slug}` before it, their branches couldn't
+/// be told apart.
+pub fn branch_template(dir: &Path) -> Result<String, PiqoError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["config", "--get", "piqo.branchTemplate"])
+        .output()?;
+    let configured = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || configured.is_empty() {
+        return Ok(DEFAULT_BRANCH_TEMPLATE.to_string());
+    }
+    match (configured.find("{id}"), configured.find("{slug}")) {
+        (Some(id), slug) if slug.is_none_or(|slug| slug > id) => Ok(configured),
+        _ => Err(PiqoError::InvalidInput(format!(
+            "invalid piqo.branchTemplate {}, it needs {{id}} before any {{slug}}",
+            configured
+        ))),
+    }
+}
+
+/// Branch name for the task following the template.
+pub fn branch_name(template: &str, task: &Task) -> String {
+    let slug = task
+        .name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+    let slug = match slug.char_indices().nth(MAX_SLUG_LEN) {
+        Some((end, _)) => slug[..end].trim_end_matches('-'),
+        None => slug.as_str(),
+    };
+    let slug = if slug.is_empty() { "task" } else { slug };
+    template
+        .replace("{id}", &task.id.to_string())
+        .replace("{slug}", slug)
+}
+
+/// The task id of a branch named following the template, like `4821` in
+/// `piqo/4821-fix-login`.
+///
+/// The text before `{id}` has to match exactly, after the id the text up to `{slug}`
+/// does, or everything without a `{slug}`.
+pub fn task_id_from_branch(template: &str, branch: &str) -> Option<u64> {
+    let (before, after) = template.split_once("{id}")?;
+    let rest = branch.strip_prefix(before)?;
+    let digits = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    let rest = &rest[digits.len()..];
+    let matches = match after.split_once("{slug}") {
+        Some((separator, _)) => rest.starts_with(separator) && rest.len() > separator.len(),
+        None => rest == after,
+    };
+    if !matches {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// The branch checked out in the repository containing `dir`, `None` on a detached `HEAD`.
+pub fn current_branch(dir: &Path) -> Result<Option<String>, PiqoError> {
+    let head = fs::read_to_string(data_storage::discover_git_dir(dir)?.join("HEAD"))?;
+    Ok(head
+        .trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(|b| b.to_string()))
+}
+
+/// The task the checked out branch is named after, if the project has it.
+pub fn current_task(p: &Project, dir: &Path) -> Option<u64> {
+    let branch = current_branch(dir).ok()??;
+    let template = branch_template(dir).ok()?;
+    task_id_from_branch(&template, &branch).filter(|id| p.get_task(*id).is_some())
+}
+
+/// Checks out the branch in the repository containing `dir`, creating it from `HEAD` when
+/// it doesn't exist. Returns whether it was created.
+pub fn switch_to(dir: &Path, branch: &str) -> Result<bool, PiqoError> {
+    let exists = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("refs/heads/{}", branch))
+        .output()?
+        .status
+        .success();
+
+    let mut switch = Command::new("git");
+    switch.arg("-C").arg(dir).arg("switch");
+    if !exists {
+        switch.arg("-c");
+    }
+    let output = switch.arg(branch).output()?;
+    if !output.status.success() {
+        return Err(PiqoError::InvalidInput(format!(
+            "git could not switch to {}: {}",
+            branch,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(!exists)
+}
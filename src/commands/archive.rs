@@ -0,0 +1,71 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use piqo::{PiqoError, data_storage, query};
+
+pub(crate) fn command() -> Command {
+    Command::new("archive")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Manage archived tasks")
+        .subcommand(
+            Command::new("purge")
+                .about("Removes tasks that have been archived for a while")
+                .arg(
+                    Arg::new("older-than")
+                        .long("older-than")
+                        .required(true)
+                        .help("how long tasks have to be archived, e.g. 90d or 12w"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("only print the tasks that would be removed"),
+                ),
+        )
+}
+
+pub(crate) fn archive(matches: &ArgMatches) -> Result<(), PiqoError> {
+    match matches.subcommand() {
+        Some(("purge", args)) => purge(args),
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+}
+
+fn purge(args: &ArgMatches) -> Result<(), PiqoError> {
+    let older_than = query::parse_duration(args.get_one::<String>("older-than").unwrap())?;
+    let dry_run = args.get_flag("dry-run");
+
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+    let now = chrono::Utc::now().timestamp();
+    let cutoff = now - older_than;
+    if cutoff >= now {
+        return Err(PiqoError::InvalidInput(format!(
+            "--older-than has to be a positive duration, got {}",
+            args.get_one::<String>("older-than").unwrap()
+        )));
+    }
+    let purged = p
+        .get_tasks_archived_before(cutoff)
+        .iter()
+        .map(|t| (t.id, t.name.to_owned()))
+        .collect::<Vec<(u64, String)>>();
+
+    for (id, name) in &purged {
+        println!(
+            "{} {} {}",
+            if dry_run { "Would remove" } else { "Removed" },
+            id,
+            name
+        );
+        if !dry_run {
+            p.remove_task(*id);
+        }
+    }
+    if purged.is_empty() {
+        println!("No tasks archived for that long");
+    } else if !dry_run {
+        super::store(&p)?;
+    }
+    Ok(())
+}
@@ -1,47 +1,158 @@
-use crate::{data_storage, models::Project};
-use clap::ArgMatches;
+use clap::{Arg, ArgMatches, Command};
 use inquire::{MultiSelect, Select};
+use piqo::{PiqoError, Project, data_storage};
 
+use super::input;
 use super::list_items::CategoryItem;
 
-pub(crate) fn prompt_categories(
-    category_matches: &ArgMatches,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn command() -> Command {
+    Command::new("categories")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Alter categories of the project")
+        .subcommand(
+            Command::new("add")
+                .about("Add categories")
+                .arg(categories_arg()),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove categories")
+                .arg(categories_arg()),
+        )
+        .subcommand(
+            Command::new("rename")
+                .visible_alias("edit")
+                .about("Renames a category")
+                .arg(Arg::new("CATEGORY").help("name or id of the category, prompts when omitted"))
+                .arg(Arg::new("NEW_NAME").help("new name, prompts when omitted")),
+        )
+        .subcommand(Command::new("list").about("Prints categories"))
+        .subcommand(
+            Command::new("print")
+                .about("Prints details of one category")
+                .arg(Arg::new("CATEGORY").help("name or id of the category, prompts when omitted")),
+        )
+}
+
+fn categories_arg() -> Arg {
+    Arg::new("CATEGORY")
+        .num_args(1..)
+        .help("category names, prompts when omitted")
+}
+
+pub(crate) fn prompt_categories(category_matches: &ArgMatches) -> Result<(), PiqoError> {
+    let lock = super::lock_unless_read_only(category_matches)?;
     let mut p = data_storage::load_project()?;
     match category_matches.subcommand() {
-        Some(("add", _)) => prompt_create_categories(&mut p)?,
-        Some(("remove", _)) => prompt_remove_categories(&mut p)?,
-        Some(("edit", _)) => prompt_edit_category(&mut p)?,
+        Some(("add", args)) => add_categories(&mut p, args)?,
+        Some(("remove", args)) => remove_categories(&mut p, args)?,
+        Some(("rename", args)) => rename_category(&mut p, args)?,
         Some(("list", _)) => p.print_categories(),
-        Some(("print", _)) => {
-            let selected =
-                Select::new("Select category:", get_categories_list(&p, false)).prompt()?;
-
-            p.print_category(selected.id);
+        Some(("print", args)) => {
+            let id = select_category(&p, args)?;
+            p.print_category(id);
         }
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
     };
-    data_storage::store_project(&p)?;
+    if lock.is_some() {
+        super::store(&p)?;
+    }
+    Ok(())
+}
+
+fn select_category(p: &Project, args: &ArgMatches) -> Result<u64, PiqoError> {
+    match args.get_one::<String>("CATEGORY") {
+        Some(category) => p.resolve_category(category),
+        None => {
+            input::ensure_interactive("category")?;
+            Ok(
+                Select::new("Select category:", get_categories_list(p, false))
+                    .prompt()?
+                    .id,
+            )
+        }
+    }
+}
+
+fn add_categories(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(names) = args.get_many::<String>("CATEGORY") else {
+        input::ensure_interactive("category names")?;
+        return prompt_create_categories(p);
+    };
+    for name in names {
+        if p.get_category_by_name(name).is_some() {
+            return Err(PiqoError::InvalidInput(format!(
+                "category with name {} already exists",
+                name
+            )));
+        }
+        p.add_category(name);
+    }
+    Ok(())
+}
+
+fn remove_categories(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(names) = args.get_many::<String>("CATEGORY") else {
+        input::ensure_interactive("category names")?;
+        return prompt_remove_categories(p);
+    };
+    let ids = names
+        .map(|name| p.resolve_category(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    for id in &ids {
+        if p.tasks.iter().any(|t| t.category == *id) {
+            return Err(PiqoError::InvalidInput(format!(
+                "category {} is not deletable as it contains tasks",
+                p.get_category_name(*id).unwrap()
+            )));
+        }
+    }
+    for id in ids {
+        p.remove_category(id);
+    }
+    Ok(())
+}
+
+fn rename_category(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    if args.get_one::<String>("CATEGORY").is_none() {
+        input::ensure_interactive("category")?;
+        return prompt_edit_category(p);
+    }
+    let id = select_category(p, args)?;
+    let new_name = match args.get_one::<String>("NEW_NAME") {
+        Some(new_name) => new_name.to_owned(),
+        None => {
+            input::ensure_interactive("new name")?;
+            inquire::Text::new("New name").prompt()?
+        }
+    };
+    if p.get_category_by_name(&new_name)
+        .is_some_and(|c| c.id != id)
+    {
+        return Err(PiqoError::InvalidInput(format!(
+            "category with name {} already exists",
+            new_name
+        )));
+    }
+    p.edit_category(id, &new_name)?;
     Ok(())
 }
 
-pub(crate) fn prompt_create_category(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_create_category(p: &mut Project) -> Result<(), PiqoError> {
     let status_name = inquire::Text::new("Category name").prompt()?;
     if p.categories.iter().any(|c| c.name == status_name) {
         println!("Category with this name already exists");
-        return Err(inquire::error::InquireError::OperationCanceled);
+        return Err(inquire::InquireError::OperationCanceled.into());
     }
     p.add_category(status_name.as_str());
     Ok(())
 }
 
-pub(crate) fn prompt_create_categories(
-    p: &mut Project,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_create_categories(p: &mut Project) -> Result<(), PiqoError> {
     prompt_create_category(p)?;
     loop {
-        let create_more =
-            Select::new("Create more categories?", vec!["Yes", "No"]).prompt()?;
+        let create_more = Select::new("Create more categories?", vec!["Yes", "No"]).prompt()?;
         if create_more == "No" {
             return Ok(());
         }
@@ -49,9 +160,7 @@ pub(crate) fn prompt_create_categories(
     }
 }
 
-pub(crate) fn prompt_remove_categories(
-    p: &mut Project,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_remove_categories(p: &mut Project) -> Result<(), PiqoError> {
     let categories = get_categories_list(p, true);
 
     let not_deletable: Vec<&CategoryItem> = categories.iter().filter(|c| c.not_deletable).collect();
@@ -69,7 +178,7 @@ pub(crate) fn prompt_remove_categories(
         "Select categories to remove",
         categories.iter().filter(|c| !c.not_deletable).collect(),
     )
-        .prompt()?;
+    .prompt()?;
 
     for i in categories_to_remove {
         p.remove_category(i.id);
@@ -77,11 +186,11 @@ pub(crate) fn prompt_remove_categories(
     Ok(())
 }
 
-pub(crate) fn prompt_edit_category(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_edit_category(p: &mut Project) -> Result<(), PiqoError> {
     let categories = get_categories_list(p, false);
     let category_to_edit = Select::new("Select category to edit", categories).prompt()?;
     let new_name = inquire::Text::new("New name").prompt()?;
-    p.edit_category(category_to_edit.id, new_name.as_str());
+    p.edit_category(category_to_edit.id, new_name.as_str())?;
     Ok(())
 }
 
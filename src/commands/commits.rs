@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use piqo::{PiqoError, commits, data_storage};
+
+pub(crate) fn sync_command() -> Command {
+    Command::new("sync-commits")
+        .about("Moves tasks that commit messages say they fix, like `fixes piqo#4821`")
+        .arg(
+            Arg::new("to").long("to").help(
+                "category to move fixed tasks to, defaults to the saved or first done category",
+            ),
+        )
+        .arg(
+            Arg::new("save")
+                .long("save")
+                .action(ArgAction::SetTrue)
+                .requires("to")
+                .help("remembers --to for later syncs"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("only print which tasks would be moved"),
+        )
+}
+
+pub(crate) fn sync(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+
+    let category = match matches.get_one::<String>("to") {
+        Some(category) => p.resolve_category(category)?,
+        None => p.get_commit_fixes_category().ok_or_else(|| {
+            PiqoError::InvalidInput(
+                "no done category to move fixed tasks to, pass one with --to".to_string(),
+            )
+        })?,
+    };
+    if matches.get_flag("save") {
+        p.set_commit_fixes_category(Some(category))?;
+    }
+
+    let commits = commits::referencing_commits(Path::new("."))?;
+    let moved = p.sync_commits(&commits, category)?;
+    let verb = if matches.get_flag("dry-run") {
+        "Would move"
+    } else {
+        "Moved"
+    };
+    let category_name = p.get_category_name(category).unwrap();
+    for (task_id, hash) in &moved {
+        println!(
+            "{} task {} to {}, fixed in {}",
+            verb,
+            task_id,
+            category_name,
+            &hash[..hash.len().min(7)]
+        );
+    }
+    if matches.get_flag("dry-run") {
+        return Ok(());
+    }
+    super::store(&p)
+}
@@ -0,0 +1,41 @@
+use clap::Command;
+use owo_colors::OwoColorize;
+use piqo::{PiqoError, PiqoPath, StorageLayout, data_storage, schema};
+
+pub(crate) fn command() -> Command {
+    Command::new("doctor").about("Checks the project file and reports pending migrations")
+}
+
+pub(crate) fn doctor() -> Result<(), PiqoError> {
+    let path = match data_storage::check_piqo_dir() {
+        PiqoPath::NotFound(err) => return Err(PiqoError::NotInGitRepo(Box::new(err))),
+        PiqoPath::FoundNotInit(path) => return Err(PiqoError::NotInitialized(path)),
+        PiqoPath::Found(path) => path,
+    };
+    println!("Path: {}", path.display());
+    println!("Layout: {}", StorageLayout::of(&path));
+
+    let value = data_storage::read_project_value(&path)?;
+    let version = schema::version_of(&value);
+    println!("Schema version: {}", version);
+    println!("Supported schema version: {}", schema::SCHEMA_VERSION);
+
+    let migrations = schema::pending_migrations(&value)?;
+    if migrations.is_empty() {
+        println!("{}", "Project is up to date".green());
+    } else {
+        println!("Migrations to run on next write:");
+        for migration in migrations {
+            println!(
+                "- {} -> {}: {}",
+                migration.from,
+                migration.from + 1,
+                migration.description
+            );
+        }
+    }
+
+    data_storage::load_project_from(&path)?;
+    println!("{}", "Project loads fine".green());
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use clap::{Arg, ArgAction, ArgMatches, value_parser};
+use piqo::{ArchivedTasks, PiqoError, Query};
+
+/// The filter expression, visibility, `--sort` and `--limit` arguments shared by the
+/// listing commands.
+pub(crate) fn args() -> [Arg; 5] {
+    [
+        Arg::new("FILTER")
+            .allow_hyphen_values(true)
+            .help("filter expression, e.g. 'category:\"In Progress\" assignee:me due<7d -archived'"),
+        Arg::new("archived")
+            .long("archived")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("all")
+            .help("only show archived tasks"),
+        Arg::new("all")
+            .long("all")
+            .action(ArgAction::SetTrue)
+            .help("show archived tasks too"),
+        Arg::new("sort")
+            .long("sort")
+            .allow_hyphen_values(true)
+            .default_value("category,priority")
+            .help("comma separated fields to sort by, prefix with - to sort descending, e.g. due,-created"),
+        Arg::new("limit")
+            .long("limit")
+            .value_parser(value_parser!(usize))
+            .help("show at most this many tasks"),
+    ]
+}
+
+pub(crate) fn query(matches: &ArgMatches) -> Result<Query, PiqoError> {
+    let expression = matches
+        .get_one::<String>("FILTER")
+        .map_or("", |s| s.as_str());
+    let archived = if matches.get_flag("archived") {
+        ArchivedTasks::Only
+    } else if matches.get_flag("all") {
+        ArchivedTasks::Included
+    } else {
+        ArchivedTasks::Hidden
+    };
+    Ok(Query::parse(expression)?
+        .with_archived(archived)
+        .sorted_by(matches.get_one::<String>("sort").unwrap())?
+        .limited_to(matches.get_one::<usize>("limit").copied()))
+}
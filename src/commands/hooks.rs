@@ -0,0 +1,275 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use piqo::{PiqoError, PiqoPath, Project, commits, data_storage};
+
+/// Hooks piqo installs, each runs `piqo hooks run <hook>`.
+const HOOKS: [&str; 3] = ["prepare-commit-msg", "commit-msg", "post-merge"];
+
+/// Marks hook files written by piqo, anything else is left alone.
+const MARKER: &str = "# Installed by piqo";
+
+/// Suffix of hooks that were there before piqo's, restored on uninstall.
+const BACKUP_SUFFIX: &str = "pre-piqo";
+
+/// Starts the comment line `prepare-commit-msg` suggests references with, `commit-msg`
+/// turns it into a `Refs` line unless it was deleted.
+const SUGGESTION: &str = "# piqo: Refs ";
+
+/// Git drops everything below this line when committing with `--verbose`.
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+pub(crate) fn command() -> Command {
+    Command::new("hooks")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Installs git hooks that tie commits to tasks")
+        .subcommand(
+            Command::new("install")
+                .about("Installs the prepare-commit-msg, commit-msg and post-merge hooks")
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("moves existing hooks aside instead of failing, they don't run until uninstall puts them back"),
+                ),
+        )
+        .subcommand(Command::new("uninstall").about("Removes the hooks installed by piqo"))
+        .subcommand(
+            Command::new("run")
+                .hide(true)
+                .about("Runs a hook, called by the installed hooks")
+                .arg(Arg::new("HOOK").required(true).value_parser(HOOKS))
+                .arg(Arg::new("ARGS").num_args(0..).help("arguments git passed to the hook")),
+        )
+}
+
+pub(crate) fn hooks(matches: &ArgMatches) -> Result<(), PiqoError> {
+    match matches.subcommand() {
+        Some(("install", args)) => install(args.get_flag("force")),
+        Some(("uninstall", _)) => uninstall(),
+        Some(("run", args)) => {
+            let hook_args = args
+                .get_many::<String>("ARGS")
+                .into_iter()
+                .flatten()
+                .map(|a| a.as_str())
+                .collect::<Vec<&str>>();
+            match args.get_one::<String>("HOOK").unwrap().as_str() {
+                "prepare-commit-msg" => prepare_commit_msg(&hook_args),
+                "commit-msg" => commit_msg(&hook_args),
+                _ => post_merge(),
+            }
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+}
+
+fn hooks_dir() -> Result<PathBuf, PiqoError> {
+    data_storage::hooks_dir(Path::new("."))
+}
+
+fn is_piqo_hook(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|contents| contents.contains(MARKER))
+}
+
+fn install(force: bool) -> Result<(), PiqoError> {
+    let dir = hooks_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let foreign = HOOKS
+        .iter()
+        .map(|hook| dir.join(hook))
+        .filter(|path| path.exists() && !is_piqo_hook(path))
+        .collect::<Vec<PathBuf>>();
+    if !foreign.is_empty() && !force {
+        let names = foreign
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<String>>();
+        return Err(PiqoError::InvalidInput(format!(
+            "hooks already exist: {}, pass --force to move them aside",
+            names.join(", ")
+        )));
+    }
+    let backups = foreign
+        .iter()
+        .map(|path| path.with_extension(BACKUP_SUFFIX))
+        .filter(|backup| backup.exists())
+        .map(|backup| backup.display().to_string())
+        .collect::<Vec<String>>();
+    if !backups.is_empty() {
+        return Err(PiqoError::InvalidInput(format!(
+            "hooks were moved aside before: {}, remove or restore them first",
+            backups.join(", ")
+        )));
+    }
+    for path in &foreign {
+        let backup = path.with_extension(BACKUP_SUFFIX);
+        fs::rename(path, &backup)?;
+        println!("Moved {} to {}", path.display(), backup.display());
+    }
+
+    for hook in HOOKS {
+        let path = dir.join(hook);
+        let script = format!(
+            "#!/bin/sh\n{}, remove with `piqo hooks uninstall`\ncommand -v piqo >/dev/null 2>&1 || exit 0\nexec piqo hooks run {} \"$@\"\n",
+            MARKER, hook
+        );
+        fs::write(&path, script)?;
+        make_executable(&path)?;
+        println!("Installed {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), PiqoError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), PiqoError> {
+    Ok(())
+}
+
+fn uninstall() -> Result<(), PiqoError> {
+    let dir = hooks_dir()?;
+    for hook in HOOKS {
+        let path = dir.join(hook);
+        if !is_piqo_hook(&path) {
+            continue;
+        }
+        fs::remove_file(&path)?;
+        println!("Removed {}", path.display());
+
+        let backup = path.with_extension(BACKUP_SUFFIX);
+        if backup.exists() {
+            fs::rename(&backup, &path)?;
+            println!("Restored the previous {} hook", hook);
+        }
+    }
+    Ok(())
+}
+
+/// Loads the project, `None` for repositories without one so the hooks don't get in the way.
+fn project_if_initialized() -> Result<Option<Project>, PiqoError> {
+    match data_storage::check_piqo_dir() {
+        PiqoPath::Found(_) => Ok(Some(data_storage::load_project()?)),
+        _ => Ok(None),
+    }
+}
+
+/// Suggests references to the tasks the committer is working on in a new commit message.
+///
+/// The suggestion is a comment, so quitting the editor without a message still aborts the
+/// commit. Messages given with `-m`, merges, squashes and amends are left alone.
+fn prepare_commit_msg(args: &[&str]) -> Result<(), PiqoError> {
+    let [file, rest @ ..] = args else {
+        return Ok(());
+    };
+    if !matches!(rest.first(), None | Some(&"template")) {
+        return Ok(());
+    }
+    let Some(p) = project_if_initialized()? else {
+        return Ok(());
+    };
+    let message = fs::read_to_string(file)?;
+    if !commits::parse_refs(&without_comments(&message)).is_empty() {
+        return Ok(());
+    }
+
+    let refs = p
+        .get_tasks_in_progress()
+        .iter()
+        .map(|t| format!("piqo#{}", t.id))
+        .collect::<Vec<String>>();
+    if refs.is_empty() {
+        return Ok(());
+    }
+    let suggestion = format!(
+        "\n{}{}\n# Delete the line above to commit without referencing these tasks.\n",
+        SUGGESTION,
+        refs.join(", ")
+    );
+    fs::write(file, suggestion + &message)?;
+    Ok(())
+}
+
+/// Rejects commit messages that reference tasks the project doesn't have and turns the
+/// suggested references into a `Refs` line once the message has content.
+fn commit_msg(args: &[&str]) -> Result<(), PiqoError> {
+    let [file, ..] = args else {
+        return Ok(());
+    };
+    let Some(p) = project_if_initialized()? else {
+        return Ok(());
+    };
+    let message = fs::read_to_string(file)?;
+    let content = without_comments(&message);
+    for task_ref in commits::parse_refs(&content) {
+        if p.get_task(task_ref.task_id).is_none() {
+            return Err(PiqoError::InvalidInput(format!(
+                "the commit message references piqo#{}, which is not a task of this project",
+                task_ref.task_id
+            )));
+        }
+    }
+
+    if content.trim().is_empty() || !commits::parse_refs(&content).is_empty() {
+        return Ok(());
+    }
+    if let Some(message) = with_suggested_refs(&message) {
+        fs::write(file, message)?;
+    }
+    Ok(())
+}
+
+/// Replaces the suggestion comment with a `Refs` line below the last line of the message,
+/// `None` without a suggestion.
+fn with_suggested_refs(message: &str) -> Option<String> {
+    let mut lines = message.lines().collect::<Vec<&str>>();
+    let suggestion = lines.iter().position(|l| l.starts_with(SUGGESTION))?;
+    let refs = format!(
+        "Refs {}",
+        lines.remove(suggestion)[SUGGESTION.len()..].trim()
+    );
+    if commits::parse_refs(&refs).is_empty() {
+        return Some(lines.join("\n") + "\n");
+    }
+    let end = lines
+        .iter()
+        .position(|l| *l == SCISSORS)
+        .unwrap_or(lines.len());
+    let last = lines[..end]
+        .iter()
+        .rposition(|l| !l.starts_with('#') && !l.trim().is_empty())?;
+    lines.splice(last + 1..last + 1, ["", refs.as_str()]);
+    Some(lines.join("\n") + "\n")
+}
+
+/// Checks that the project still loads after a merge, git can't undo the merge anymore
+/// but the user gets told right away.
+fn post_merge() -> Result<(), PiqoError> {
+    if let PiqoPath::Found(path) = data_storage::check_piqo_dir()
+        && let Err(err) = data_storage::load_project_from(&path)
+    {
+        return Err(PiqoError::InvalidInput(format!(
+            "the project is broken after the merge, fix it before committing: {}",
+            err
+        )));
+    }
+    Ok(())
+}
+
+/// Git drops lines starting with `#` from commit messages, so do we.
+fn without_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
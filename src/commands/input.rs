@@ -0,0 +1,51 @@
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+
+use chrono::{Local, NaiveDate, TimeZone};
+
+use piqo::{PiqoError, Project, query};
+
+pub(crate) fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+/// Fails with a helpful message when a value is missing and we can't prompt for it.
+pub(crate) fn ensure_interactive(missing: &str) -> Result<(), PiqoError> {
+    if is_interactive() {
+        return Ok(());
+    }
+    Err(PiqoError::MissingInput(missing.to_string()))
+}
+
+pub(crate) fn resolve_task(p: &Project, id: &str) -> Result<u64, PiqoError> {
+    let id: u64 = id
+        .trim()
+        .parse()
+        .map_err(|_| PiqoError::InvalidInput(format!("invalid task id: {}", id)))?;
+    match p.get_task(id) {
+        Some(task) => Ok(task.id),
+        None => Err(PiqoError::TaskNotFound(id)),
+    }
+}
+
+/// Reads a file, `-` reads from stdin.
+pub(crate) fn read_text_file(path: &Path) -> Result<String, PiqoError> {
+    if path == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Parses a date or a duration ago into a unix timestamp.
+pub(crate) fn parse_since(since: &str) -> Result<i64, PiqoError> {
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .map(|d| d.timestamp())
+            .ok_or_else(|| PiqoError::InvalidInput(format!("invalid local date: {}", since)));
+    }
+    Ok(chrono::Utc::now().timestamp() - query::parse_duration(since)?)
+}
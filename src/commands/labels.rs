@@ -0,0 +1,119 @@
+use clap::{Arg, ArgMatches, Command, value_parser};
+use inquire::{MultiSelect, Select};
+use piqo::{Label, LabelColor, PiqoError, Project, data_storage};
+
+use super::input;
+
+pub(crate) fn command() -> Command {
+    Command::new("labels")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Alter labels of the project")
+        .subcommand(
+            Command::new("add")
+                .about("Add labels")
+                .arg(labels_arg())
+                .arg(color_arg()),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove labels, untagging their tasks")
+                .arg(labels_arg()),
+        )
+        .subcommand(
+            Command::new("rename")
+                .visible_alias("edit")
+                .about("Renames or recolors a label")
+                .arg(Arg::new("LABEL").help("name or id of the label, prompts when omitted"))
+                .arg(Arg::new("NEW_NAME").help("new name, prompts when omitted without --color"))
+                .arg(color_arg()),
+        )
+        .subcommand(Command::new("list").about("Prints labels"))
+}
+
+fn labels_arg() -> Arg {
+    Arg::new("LABEL")
+        .num_args(1..)
+        .help("label names, prompts when omitted")
+}
+
+fn color_arg() -> Arg {
+    Arg::new("color")
+        .long("color")
+        .value_parser(value_parser!(LabelColor))
+        .help("red, green, yellow, blue, magenta, cyan, white or gray")
+}
+
+pub(crate) fn prompt_labels(label_matches: &ArgMatches) -> Result<(), PiqoError> {
+    let lock = super::lock_unless_read_only(label_matches)?;
+    let mut p = data_storage::load_project()?;
+    match label_matches.subcommand() {
+        Some(("add", args)) => add_labels(&mut p, args)?,
+        Some(("remove", args)) => remove_labels(&mut p, args)?,
+        Some(("rename", args)) => rename_label(&mut p, args)?,
+        Some(("list", _)) => p.print_labels(),
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    };
+    if lock.is_some() {
+        super::store(&p)?;
+    }
+    Ok(())
+}
+
+fn add_labels(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let color = args.get_one::<LabelColor>("color").copied();
+    let Some(names) = args.get_many::<String>("LABEL") else {
+        input::ensure_interactive("label names")?;
+        let name = inquire::Text::new("Label name").prompt()?;
+        p.add_label(&name, color)?;
+        return Ok(());
+    };
+    for name in names {
+        p.add_label(name, color)?;
+    }
+    Ok(())
+}
+
+fn remove_labels(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let ids = match args.get_many::<String>("LABEL") {
+        Some(names) => names
+            .map(|name| p.resolve_label(name))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => {
+            input::ensure_interactive("label names")?;
+            MultiSelect::new("Select labels to remove", p.labels.clone())
+                .prompt()?
+                .iter()
+                .map(|l| l.id)
+                .collect()
+        }
+    };
+    for id in ids {
+        p.remove_label(id)?;
+    }
+    Ok(())
+}
+
+fn rename_label(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let id = match args.get_one::<String>("LABEL") {
+        Some(label) => p.resolve_label(label)?,
+        None => {
+            input::ensure_interactive("label")?;
+            select_label(p)?.id
+        }
+    };
+    let color = args.get_one::<LabelColor>("color").copied();
+    let new_name = match args.get_one::<String>("NEW_NAME") {
+        Some(new_name) => Some(new_name.to_owned()),
+        None if color.is_some() => None,
+        None => {
+            input::ensure_interactive("new name")?;
+            Some(inquire::Text::new("New name").prompt()?)
+        }
+    };
+    p.edit_label(id, new_name.as_deref(), color)
+}
+
+fn select_label(p: &Project) -> Result<Label, PiqoError> {
+    Ok(Select::new("Select label", p.labels.clone()).prompt()?)
+}
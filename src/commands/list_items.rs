@@ -8,10 +8,9 @@ pub struct TaskItem {
 
 impl Display for TaskItem {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if self.category.is_none() {
-            write!(f, "{}", self.name)
-        } else {
-            write!(f, "{} ({})", self.name, self.category.as_ref().unwrap())
+        match &self.category {
+            Some(category) => write!(f, "{} ({})", self.name, category),
+            None => write!(f, "{}", self.name),
         }
     }
 }
@@ -35,11 +34,15 @@ impl Display for CategoryItem {
 pub struct UserItem {
     pub name: String,
     pub git_email: String,
+    pub commits: usize,
 }
 
 impl Display for UserItem {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{} <{}>", self.name, self.git_email)
+        match self.commits {
+            1 => write!(f, "{} <{}> (1 commit)", self.name, self.git_email),
+            n => write!(f, "{} <{}> ({} commits)", self.name, self.git_email, n),
+        }
     }
 }
 
@@ -0,0 +1,37 @@
+use clap::{Arg, ArgMatches, Command, value_parser};
+use piqo::{PiqoError, data_storage};
+
+pub(crate) fn command() -> Command {
+    Command::new("log")
+        .about("Shows the change history of the project or of a single task")
+        .arg(Arg::new("ID").help("task id, also works for removed tasks"))
+        .arg(
+            Arg::new("max-count")
+                .short('n')
+                .long("max-count")
+                .value_parser(value_parser!(usize))
+                .help("only show the latest changes"),
+        )
+}
+
+pub(crate) fn log(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let p = data_storage::load_project()?;
+
+    let task_id = match matches.get_one::<String>("ID") {
+        Some(id) => {
+            let id: u64 = id
+                .trim()
+                .parse()
+                .map_err(|_| PiqoError::InvalidInput(format!("invalid task id: {}", id)))?;
+            // Removed tasks are gone from the project but not from the history
+            if p.get_task(id).is_none() && !p.history.iter().any(|e| e.task_id() == Some(id)) {
+                return Err(PiqoError::TaskNotFound(id));
+            }
+            Some(id)
+        }
+        None => None,
+    };
+
+    p.print_history(task_id, matches.get_one::<usize>("max-count").copied());
+    Ok(())
+}
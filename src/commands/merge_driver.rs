@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgMatches, Command, value_parser};
+use piqo::{PiqoError, merge, schema};
+use serde_json::Value;
+
+pub(crate) fn command() -> Command {
+    Command::new("merge-driver")
+        .about("Merges two versions of a .piqo file, meant to be run by git")
+        .long_about(
+            "Merges two versions of a .piqo file, meant to be run by git.\n\n\
+             Register it with:\n  \
+             git config merge.piqo.driver \"piqo merge-driver %O %A %B\"\n  \
+             echo '.piqo merge=piqo' >> .gitattributes",
+        )
+        .arg(path_arg("BASE", "common ancestor version (%O)"))
+        .arg(path_arg(
+            "OURS",
+            "current version, receives the result (%A)",
+        ))
+        .arg(path_arg("THEIRS", "other branch's version (%B)"))
+}
+
+fn path_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .required(true)
+        .value_parser(value_parser!(PathBuf))
+        .help(help)
+}
+
+pub(crate) fn merge_driver(args: &ArgMatches) -> Result<(), PiqoError> {
+    let base_path = args.get_one::<PathBuf>("BASE").unwrap();
+    let ours_path = args.get_one::<PathBuf>("OURS").unwrap();
+    let theirs_path = args.get_one::<PathBuf>("THEIRS").unwrap();
+
+    let mut base = read_json(base_path)?;
+    let mut ours = read_json(ours_path)?.unwrap_or(Value::Null);
+    let mut theirs = read_json(theirs_path)?.unwrap_or(Value::Null);
+
+    // Bring whole projects to the same schema so old and new fields line up
+    for value in [base.as_mut(), Some(&mut ours), Some(&mut theirs)]
+        .into_iter()
+        .flatten()
+    {
+        if value.get("categories").is_some() {
+            schema::migrate(value)?;
+        }
+    }
+
+    let merged = merge::merge_values(base.as_ref(), &ours, &theirs);
+    fs::write(ours_path, merged.to_string_with_markers())?;
+
+    if merged.is_clean() {
+        return Ok(());
+    }
+    Err(PiqoError::MergeConflicts(
+        merged.conflicts.into_iter().map(|c| c.path).collect(),
+    ))
+}
+
+/// Reads a version of the file, `None` if it is empty as git gives us for a missing ancestor.
+fn read_json(path: &Path) -> Result<Option<Value>, PiqoError> {
+    let content = fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))
+}
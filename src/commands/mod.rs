@@ -1,25 +1,45 @@
 use std::env;
-
-use crate::{commands::categories::prompt_create_categories, utils};
-use clap::{command, Arg, ArgAction, Command};
-
-use crate::{data_storage, models::Project};
-
-pub mod categories;
-pub mod list_items;
-pub mod tasks;
-pub mod users;
-
-pub(crate) fn parse() -> Result<(), inquire::error::InquireError> {
+use std::path::Path;
+
+use crate::commands::categories::prompt_create_categories;
+use clap::{Arg, ArgAction, ArgMatches, Command, command};
+use piqo::utils;
+
+use piqo::{Journal, PiqoError, Project, ProjectLock, Query, branches, data_storage, journal};
+
+pub(crate) mod archive;
+pub(crate) mod categories;
+pub(crate) mod commits;
+pub(crate) mod doctor;
+mod filter;
+pub(crate) mod hooks;
+mod input;
+pub(crate) mod labels;
+pub(crate) mod list_items;
+pub(crate) mod log;
+pub(crate) mod merge_driver;
+pub(crate) mod storage;
+pub(crate) mod tasks;
+pub(crate) mod time;
+pub(crate) mod transfer;
+pub(crate) mod undo;
+pub(crate) mod users;
+
+pub(crate) fn parse() -> Result<(), PiqoError> {
     let command = command!() // requires `cargo` feature
         .propagate_version(true)
         .subcommand_required(true)
         .arg_required_else_help(true)
         .subcommand(Command::new("init").about("Initializes new project"))
-        .subcommand(Command::new("me").about("View your status in the project"))
+        .subcommand(
+            Command::new("me")
+                .about("View your status in the project")
+                .args(filter::args()),
+        )
         .subcommand(
             Command::new("list")
                 .about("Lists project tasks")
+                .args(filter::args())
                 .arg(
                     Arg::new("json")
                         .long("json")
@@ -35,44 +55,30 @@ pub(crate) fn parse() -> Result<(), inquire::error::InquireError> {
                 ),
         )
         .subcommand(Command::new("status").about("Prints status of project"))
-        .subcommand(
-            Command::new("categories")
-                .arg_required_else_help(true)
-                .about("Alter categories of the project")
-                .subcommand(Command::new("add").about("Add categories"))
-                .subcommand(Command::new("remove").about("Remove categories"))
-                .subcommand(Command::new("edit").about("Edits categories"))
-                .subcommand(Command::new("list").about("Prints categories")),
-        ) // .subcommand(Command::new("print").about("Prints details of one category")),
-        .subcommand(
-            Command::new("tasks")
-                .subcommand_required(true)
-                .arg_required_else_help(true)
-                .about("Alter tasks of the project")
-                .subcommand(Command::new("add").about("Adds new task"))
-                .subcommand(Command::new("remove").about("Removes task"))
-                .subcommand(Command::new("assign").about("Assigns task to user"))
-                .subcommand(Command::new("unassign").about("Unassigns task from user"))
-                .subcommand(Command::new("move").about("Moves tasks to another category"))
-                .subcommand(Command::new("edit").about("Edits task"))
-                .subcommand(Command::new("list").about("Prints tasks"))
-                .subcommand(Command::new("print").about("Prints details of one task")),
-        )
-        .subcommand(
-            Command::new("users")
-                .arg_required_else_help(true)
-                .about("Alter users of project")
-                .subcommand(Command::new("add").about("Adds new users"))
-                .subcommand(Command::new("remove").about("Removes users"))
-                .subcommand(Command::new("edit").about("Edits users"))
-                .subcommand(Command::new("assign").about("Assign users to tasks"))
-                .subcommand(Command::new("list").about("Lists users")), // .subcommand(Command::new("print")),
-        );
+        .subcommand(categories::command())
+        .subcommand(labels::command())
+        .subcommand(tasks::command())
+        .subcommand(users::command())
+        .subcommand(time::start_command())
+        .subcommand(time::stop_command())
+        .subcommand(time::log_time_command())
+        .subcommand(time::report_command())
+        .subcommand(commits::sync_command())
+        .subcommand(transfer::export_command())
+        .subcommand(transfer::import_command())
+        .subcommand(archive::command())
+        .subcommand(log::command())
+        .subcommand(undo::undo_command())
+        .subcommand(undo::redo_command())
+        .subcommand(storage::command())
+        .subcommand(doctor::command())
+        .subcommand(hooks::command())
+        .subcommand(merge_driver::command());
 
     let matches = command.get_matches();
     match matches.subcommand() {
         Some(("init", _)) => init()?,
-        Some(("me", _)) => {
+        Some(("me", sub_matches)) => {
             let p = data_storage::load_project()?;
 
             let user = match utils::get_local_git_email() {
@@ -92,7 +98,15 @@ pub(crate) fn parse() -> Result<(), inquire::error::InquireError> {
                 }
             };
 
-            p.print_user_status(user_id)
+            if let Some(task_id) = branches::current_task(&p, Path::new(".")) {
+                println!("Current task, going by the checked out branch:");
+                p.print_single_task(task_id);
+                println!();
+            }
+
+            let query =
+                filter::query(sub_matches)?.and(Query::parse(&format!("assignee:{}", user_id))?);
+            p.print_user_status(&p.query(&query)?)
         }
         Some(("status", _)) => {
             let p = data_storage::load_project()?;
@@ -101,18 +115,35 @@ pub(crate) fn parse() -> Result<(), inquire::error::InquireError> {
         }
         Some(("list", sync_matches)) => {
             let p = data_storage::load_project()?;
+            let tasks = p.query(&filter::query(sync_matches)?)?;
 
             if sync_matches.get_flag("json") {
-                p.print_tasks_json();
+                p.print_tasks_json(&tasks);
             } else if sync_matches.get_flag("details") {
-                p.print_tasks_detailed();
+                p.print_tasks_detailed(&tasks);
             } else {
-                p.print_tasks();
+                p.print_tasks(&tasks);
             }
         }
         Some(("categories", sub_matches)) => categories::prompt_categories(sub_matches)?,
+        Some(("labels", sub_matches)) => labels::prompt_labels(sub_matches)?,
         Some(("tasks", sub_matches)) => tasks::prompt_tasks(sub_matches)?,
         Some(("users", sub_matches)) => users::prompt_users(sub_matches)?,
+        Some(("start", sub_matches)) => time::start(sub_matches)?,
+        Some(("stop", _)) => time::stop()?,
+        Some(("log-time", sub_matches)) => time::log_time(sub_matches)?,
+        Some(("report", sub_matches)) => time::report(sub_matches)?,
+        Some(("sync-commits", sub_matches)) => commits::sync(sub_matches)?,
+        Some(("export", sub_matches)) => transfer::export(sub_matches)?,
+        Some(("import", sub_matches)) => transfer::import(sub_matches)?,
+        Some(("archive", sub_matches)) => archive::archive(sub_matches)?,
+        Some(("log", sub_matches)) => log::log(sub_matches)?,
+        Some(("undo", sub_matches)) => undo::undo(sub_matches)?,
+        Some(("redo", sub_matches)) => undo::redo(sub_matches)?,
+        Some(("doctor", _)) => doctor::doctor()?,
+        Some(("hooks", sub_matches)) => hooks::hooks(sub_matches)?,
+        Some(("storage", sub_matches)) => storage::storage(sub_matches)?,
+        Some(("merge-driver", sub_matches)) => merge_driver::merge_driver(sub_matches)?,
         _ => {
             println!("unknown command")
         }
@@ -121,27 +152,46 @@ pub(crate) fn parse() -> Result<(), inquire::error::InquireError> {
     Ok(())
 }
 
-fn init() -> Result<(), inquire::error::InquireError> {
+/// Subcommands that only print the project don't need to lock or store it.
+pub(crate) fn lock_unless_read_only(
+    sub_matches: &ArgMatches,
+) -> Result<Option<ProjectLock>, PiqoError> {
+    match sub_matches.subcommand_name() {
+        Some("list" | "print") => Ok(None),
+        _ => data_storage::lock_project().map(Some),
+    }
+}
+
+/// Stores the project and journals what the command changed so it can be undone.
+pub(crate) fn store(p: &Project) -> Result<(), PiqoError> {
+    data_storage::store_project(p)?;
+
+    let path = journal::journal_path(Path::new("."))?;
+    let mut journal = Journal::load(&path)?;
+    let command = env::args().skip(1).collect::<Vec<String>>().join(" ");
+    journal.record(p, &format!("piqo {}", command));
+    journal.store(&path)
+}
+
+fn init() -> Result<(), PiqoError> {
     let piqo_path = data_storage::check_piqo_dir();
 
-    println!("piqo_path: ");
     match piqo_path {
         data_storage::PiqoPath::FoundNotInit(piqo_path) => {
             println!("Initializing project at: {}", piqo_path.to_str().unwrap());
         }
         data_storage::PiqoPath::Found(piqo_path) => {
-            println!("Found piqo dir at {}", piqo_path.to_str().unwrap());
-            println!("Project already initialized");
-            return Ok(());
+            return Err(PiqoError::AlreadyInitialized(piqo_path));
         }
         data_storage::PiqoPath::NotFound(err) => {
-            return Err(inquire::InquireError::Custom(err.into()));
+            return Err(PiqoError::NotInGitRepo(Box::new(err)));
         }
     }
+    let _lock = data_storage::lock_project()?;
     let initial_project_name = match env::current_dir() {
         Ok(path) => path
             .iter()
-            .last()
+            .next_back()
             .unwrap()
             .to_os_string()
             .into_string()
@@ -154,6 +204,7 @@ fn init() -> Result<(), inquire::error::InquireError> {
         .prompt()?;
 
     let mut p = Project::new(name);
+    p.set_actor(utils::get_local_git_email());
 
     let create_categories =
         inquire::Select::new("Set initial categories", vec!["Default", "Custom"]).prompt()?;
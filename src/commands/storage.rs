@@ -0,0 +1,51 @@
+use clap::{Arg, ArgMatches, Command};
+use piqo::{PiqoError, PiqoPath, StorageLayout, data_storage};
+
+pub(crate) fn command() -> Command {
+    Command::new("storage")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Inspect or change how the project is stored")
+        .subcommand(Command::new("info").about("Prints where and how the project is stored"))
+        .subcommand(
+            Command::new("migrate")
+                .about("Converts the project to another storage layout")
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .required(true)
+                        .value_parser(["dir", "file"])
+                        .help("dir stores one file per task in a .piqo directory, file a single .piqo file"),
+                ),
+        )
+}
+
+pub(crate) fn storage(sub_matches: &ArgMatches) -> Result<(), PiqoError> {
+    let path = match data_storage::check_piqo_dir() {
+        PiqoPath::NotFound(err) => return Err(PiqoError::NotInGitRepo(Box::new(err))),
+        PiqoPath::FoundNotInit(path) => return Err(PiqoError::NotInitialized(path)),
+        PiqoPath::Found(path) => path,
+    };
+
+    match sub_matches.subcommand() {
+        Some(("info", _)) => {
+            println!("Path: {}", path.display());
+            println!("Layout: {}", StorageLayout::of(&path));
+        }
+        Some(("migrate", args)) => {
+            let to = match args.get_one::<String>("to").unwrap().as_str() {
+                "dir" => StorageLayout::Dir,
+                _ => StorageLayout::File,
+            };
+            if StorageLayout::of(&path) == to {
+                println!("Project already uses the {} layout", to);
+                return Ok(());
+            }
+            let _lock = data_storage::lock_project()?;
+            data_storage::migrate_layout(&path, to)?;
+            println!("Migrated project to the {} layout", to);
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+    Ok(())
+}
@@ -1,43 +1,866 @@
-use chrono::{DateTime, NaiveDateTime, NaiveTime, TimeZone, Timelike};
-use clap::ArgMatches;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
 
 use inquire::validator::Validation;
 use inquire::{CustomType, DateSelect, MultiSelect, Select};
 
-use super::super::data_storage;
-use super::super::models::Project;
+use owo_colors::OwoColorize;
+use piqo::{
+    Estimate, PiqoError, Priority, Project, Recurrence, branches, commits, data_storage, utils,
+};
 
+use super::input;
 use super::list_items::{DueTime, TaskItem};
 use super::{categories, users};
 
-pub(crate) fn prompt_tasks(task_matches: &ArgMatches) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn command() -> Command {
+    Command::new("tasks")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Alter tasks of the project")
+        .subcommand(
+            Command::new("add")
+                .about("Adds new task")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .help("name of the task, prompts for tasks when omitted"),
+                )
+                .args(description_args())
+                .arg(
+                    Arg::new("category")
+                        .short('c')
+                        .long("category")
+                        .help("category name or id, defaults to the default category"),
+                )
+                .arg(
+                    Arg::new("assign")
+                        .short('a')
+                        .long("assign")
+                        .action(ArgAction::Append)
+                        .help("email, name or id of user to assign"),
+                )
+                .arg(due_arg())
+                .arg(priority_arg())
+                .arg(estimate_arg())
+                .arg(recur_arg())
+                .arg(
+                    Arg::new("label")
+                        .short('l')
+                        .long("label")
+                        .action(ArgAction::Append)
+                        .help("name or id of a label to tag the task with"),
+                )
+                .arg(parent_arg()),
+        )
+        .subcommand(Command::new("remove").about("Removes tasks").arg(ids_arg()))
+        .subcommand(
+            Command::new("archive")
+                .about("Archives tasks, hiding them from listings")
+                .arg(ids_arg()),
+        )
+        .subcommand(
+            Command::new("unarchive")
+                .about("Restores archived tasks")
+                .arg(ids_arg()),
+        )
+        .subcommand(link_command(
+            "link",
+            "Makes a task block another, e.g. link 12 blocks 34",
+        ))
+        .subcommand(link_command("unlink", "Removes a blocking relationship"))
+        .subcommand(assign_command("assign", "Assigns task to users"))
+        .subcommand(assign_command("unassign", "Unassigns task from users"))
+        .subcommand(
+            Command::new("start")
+                .about("Starts working on a task: checks out its branch, assigns you and moves it to in progress")
+                .arg(id_arg())
+                .arg(
+                    Arg::new("to")
+                        .short('t')
+                        .long("to")
+                        .help("category name or id to move the task to, defaults to the in progress one"),
+                )
+                .arg(
+                    Arg::new("no-branch")
+                        .long("no-branch")
+                        .action(ArgAction::SetTrue)
+                        .help("stays on the current branch"),
+                ),
+        )
+        .subcommand(
+            Command::new("move")
+                .about("Moves tasks to another category")
+                .arg(ids_arg())
+                .arg(
+                    Arg::new("to")
+                        .short('t')
+                        .long("to")
+                        .help("category name or id to move the tasks to"),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Edits task, prompts for the field to edit when no flags are given")
+                .arg(id_arg())
+                .arg(Arg::new("name").short('n').long("name").help("new name"))
+                .args(description_args())
+                .arg(due_arg().conflicts_with("clear-due"))
+                .arg(priority_arg().conflicts_with("clear-priority"))
+                .arg(estimate_arg().conflicts_with("clear-estimate"))
+                .arg(recur_arg().conflicts_with("clear-recurrence"))
+                .arg(
+                    Arg::new("clear-recurrence")
+                        .long("clear-recurrence")
+                        .action(ArgAction::SetTrue)
+                        .help("stops the task from repeating"),
+                )
+                .arg(
+                    Arg::new("clear-estimate")
+                        .long("clear-estimate")
+                        .action(ArgAction::SetTrue)
+                        .help("clears the estimate"),
+                )
+                .arg(
+                    Arg::new("clear-priority")
+                        .long("clear-priority")
+                        .action(ArgAction::SetTrue)
+                        .help("clears the priority"),
+                )
+                .arg(parent_arg().conflicts_with("clear-parent"))
+                .arg(
+                    Arg::new("clear-parent")
+                        .long("clear-parent")
+                        .action(ArgAction::SetTrue)
+                        .help("makes the task a top level task"),
+                )
+                .arg(
+                    Arg::new("clear-due")
+                        .long("clear-due")
+                        .action(ArgAction::SetTrue)
+                        .help("clears the due date"),
+                )
+                .arg(
+                    Arg::new("add-check")
+                        .long("add-check")
+                        .action(ArgAction::Append)
+                        .help("adds a checklist item"),
+                )
+                .arg(
+                    Arg::new("remove-check")
+                        .long("remove-check")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(u64))
+                        .help("removes the checklist item with the given index"),
+                )
+                .arg(
+                    Arg::new("rename-check")
+                        .long("rename-check")
+                        .num_args(2)
+                        .value_names(["INDEX", "NAME"])
+                        .help("renames the checklist item with the given index"),
+                )
+                .arg(
+                    Arg::new("move-check")
+                        .long("move-check")
+                        .num_args(2)
+                        .value_names(["INDEX", "POSITION"])
+                        .value_parser(value_parser!(u64))
+                        .help(
+                            "moves the checklist item to a position in the list, counting from 1",
+                        ),
+                ),
+        )
+        .subcommand(label_command("label", "Tags a task with labels"))
+        .subcommand(label_command("unlabel", "Removes labels from a task"))
+        .subcommand(check_command("check", "Checks checklist items of a task"))
+        .subcommand(check_command(
+            "uncheck",
+            "Unchecks checklist items of a task",
+        ))
+        .subcommand(
+            Command::new("promote")
+                .about("Turns a checklist item into a subtask")
+                .arg(Arg::new("ID").required(true).help("id of the task"))
+                .arg(
+                    Arg::new("INDEX")
+                        .required(true)
+                        .value_parser(value_parser!(u64))
+                        .help("index of the checklist item"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("Prints tasks")
+                .args(super::filter::args()),
+        )
+        .subcommand(
+            Command::new("print")
+                .about("Prints details of one task")
+                .arg(id_arg()),
+        )
+}
+
+pub(crate) fn assign_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(id_arg())
+        .arg(users_arg())
+}
+
+fn label_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name).about(about).arg(id_arg()).arg(
+        Arg::new("LABEL")
+            .num_args(1..)
+            .help("names or ids of the labels, prompts when omitted"),
+    )
+}
+
+fn check_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name).about(about).arg(id_arg()).arg(
+        Arg::new("INDEX")
+            .num_args(1..)
+            .value_parser(value_parser!(u64))
+            .help("indexes of the checklist items, prompts when omitted"),
+    )
+}
+
+fn link_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(Arg::new("TASK").required(true).help("id of the first task"))
+        .arg(
+            Arg::new("RELATION")
+                .required(true)
+                .value_parser(["blocks", "blocked-by"]),
+        )
+        .arg(
+            Arg::new("OTHER")
+                .required(true)
+                .help("id of the second task"),
+        )
+}
+
+fn priority_arg() -> Arg {
+    Arg::new("priority")
+        .short('p')
+        .long("priority")
+        .value_parser(value_parser!(Priority))
+        .help("priority of the task, P0 to P3 with P0 being the most urgent")
+}
+
+fn estimate_arg() -> Arg {
+    Arg::new("estimate")
+        .short('e')
+        .long("estimate")
+        .value_parser(value_parser!(Estimate))
+        .help("expected effort, time like 4h or 1h30m, or story points like 3pts")
+}
+
+fn recur_arg() -> Arg {
+    Arg::new("recur")
+        .long("recur")
+        .value_parser(value_parser!(Recurrence))
+        .help("repeats the task once finished: daily, weekly, monthly, yearly or e.g. \"every 2 weeks\"")
+}
+
+fn parent_arg() -> Arg {
+    Arg::new("parent")
+        .long("parent")
+        .help("id of the task to make this a subtask of")
+}
+
+fn id_arg() -> Arg {
+    Arg::new("ID").help("id of the task, prompts when omitted")
+}
+
+fn ids_arg() -> Arg {
+    Arg::new("ID")
+        .num_args(1..)
+        .help("ids of the tasks, prompts when omitted")
+}
+
+fn users_arg() -> Arg {
+    Arg::new("USER")
+        .num_args(1..)
+        .help("emails, names or ids of the users, prompts when omitted")
+}
+
+fn due_arg() -> Arg {
+    Arg::new("due")
+        .long("due")
+        .help("due date, e.g. 2024-05-01 or \"2024-05-01 12:00\"")
+}
+
+fn description_args() -> [Arg; 2] {
+    [
+        Arg::new("description")
+            .short('d')
+            .long("description")
+            .conflicts_with("description-file")
+            .help("description of the task"),
+        Arg::new("description-file")
+            .long("description-file")
+            .value_parser(value_parser!(PathBuf))
+            .help("reads the description from a file, `-` reads stdin"),
+    ]
+}
+
+pub(crate) fn prompt_tasks(task_matches: &ArgMatches) -> Result<(), PiqoError> {
+    let lock = super::lock_unless_read_only(task_matches)?;
     let mut p = data_storage::load_project()?;
     match task_matches.subcommand() {
-        Some(("add", _)) => prompt_create_tasks(&mut p)?,
-        Some(("archive", _)) => prompt_archive_tasks(&mut p)?,
-        Some(("assign", _)) => users::prompt_assign_users(&mut p)?,
-        Some(("unassign", _)) => users::prompt_unassign_users(&mut p)?,
-        Some(("move", _)) => prompt_move_tasks(&mut p)?,
-        Some(("edit", _)) => prompt_edit_task(&mut p)?,
-        Some(("list", _)) => p.print_tasks(),
-        Some(("remove", _)) => prompt_remove_tasks(&mut p)?,
-        Some(("print", _)) => {
-            let selected_task = Select::new("Select task:", get_tasks_list(&p))
-                .prompt()
-                .unwrap();
-            p.print_single_task(selected_task.id);
+        Some(("add", args)) => create_tasks(&mut p, args)?,
+        Some(("archive", args)) => archive_tasks(&mut p, args)?,
+        Some(("unarchive", args)) => unarchive_tasks(&mut p, args)?,
+        Some(("label", args)) => label_task(&mut p, args, true)?,
+        Some(("unlabel", args)) => label_task(&mut p, args, false)?,
+        Some(("check", args)) => check_items(&mut p, args, true)?,
+        Some(("uncheck", args)) => check_items(&mut p, args, false)?,
+        Some(("promote", args)) => promote_checklist_item(&mut p, args)?,
+        Some(("link", args)) => link_tasks(&mut p, args, true)?,
+        Some(("unlink", args)) => link_tasks(&mut p, args, false)?,
+        Some(("assign", args)) => assign_task(&mut p, args)?,
+        Some(("unassign", args)) => unassign_task(&mut p, args)?,
+        Some(("start", args)) => start_task(&mut p, args)?,
+        Some(("move", args)) => move_tasks(&mut p, args)?,
+        Some(("edit", args)) => edit_task(&mut p, args)?,
+        Some(("list", args)) => p.print_tasks(&p.query(&super::filter::query(args)?)?),
+        Some(("remove", args)) => remove_tasks(&mut p, args)?,
+        Some(("print", args)) => {
+            let task_id = select_task(&p, args, "Select task:")?;
+            p.print_single_task(task_id);
+            // Listing commits is a nicety, a broken git shouldn't fail printing the task
+            let commits = commits::referencing_commits(Path::new(".")).unwrap_or_default();
+            p.print_task_commits(task_id, &commits);
         }
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
     };
-    data_storage::store_project(&p)?;
+    if lock.is_some() {
+        super::store(&p)?;
+    }
+    Ok(())
+}
+
+/// Returns the task given as `ID`, the one the checked out branch is named after or prompts
+/// for one.
+pub(crate) fn select_task(p: &Project, args: &ArgMatches, msg: &str) -> Result<u64, PiqoError> {
+    match args.get_one::<String>("ID") {
+        Some(id) => input::resolve_task(p, id),
+        None => {
+            if let Some(task_id) = branches::current_task(p, Path::new(".")) {
+                return Ok(task_id);
+            }
+            input::ensure_interactive("task id")?;
+            Ok(Select::new(msg, get_tasks_list(p)).prompt()?.id)
+        }
+    }
+}
+
+/// Returns the tasks given as `ID`s, `None` if none were given.
+fn given_tasks(p: &Project, args: &ArgMatches) -> Result<Option<Vec<u64>>, PiqoError> {
+    match args.get_many::<String>("ID") {
+        Some(ids) => Ok(Some(
+            ids.map(|id| input::resolve_task(p, id))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn given_users(p: &Project, args: &ArgMatches) -> Result<Option<Vec<u64>>, PiqoError> {
+    match args.get_many::<String>("USER") {
+        Some(users) => Ok(Some(
+            users
+                .map(|u| p.resolve_user(u))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn given_description(args: &ArgMatches) -> Result<Option<String>, PiqoError> {
+    if let Some(description) = args.get_one::<String>("description") {
+        return Ok(Some(description.to_owned()));
+    }
+    match args.get_one::<PathBuf>("description-file") {
+        Some(path) => Ok(Some(input::read_text_file(path)?)),
+        None => Ok(None),
+    }
+}
+
+fn create_tasks(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(name) = args.get_one::<String>("name") else {
+        input::ensure_interactive("--name")?;
+        return prompt_create_tasks(p);
+    };
+
+    let category = args
+        .get_one::<String>("category")
+        .map(|c| p.resolve_category(c))
+        .transpose()?;
+    let users = match args.get_many::<String>("assign") {
+        Some(users) => users
+            .map(|u| p.resolve_user(u))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
+    let due_date = args
+        .get_one::<String>("due")
+        .map(|d| utils::parse_due_date(d))
+        .transpose()?;
+    let description = given_description(args)?;
+    let parent = args
+        .get_one::<String>("parent")
+        .map(|t| input::resolve_task(p, t))
+        .transpose()?;
+
+    let labels = args
+        .get_many::<String>("label")
+        .into_iter()
+        .flatten()
+        .map(|l| p.resolve_label(l))
+        .collect::<Result<Vec<u64>, _>>()?;
+
+    let task_id = p.add_task(name.to_owned());
+    for label in labels {
+        p.label_task(task_id, label)?;
+    }
+    if let Some(priority) = args.get_one::<Priority>("priority") {
+        p.set_task_priority(task_id, *priority)?;
+    }
+    if let Some(estimate) = args.get_one::<Estimate>("estimate") {
+        p.set_task_estimate(task_id, *estimate)?;
+    }
+    if parent.is_some() {
+        p.set_parent(task_id, parent)?;
+    }
+    if let Some(category) = category {
+        p.move_task(task_id, category)?;
+    }
+    if let Some(description) = description {
+        p.edit_task_description(task_id, description)?;
+    }
+    if let Some(due_date) = due_date {
+        p.set_task_due_date(task_id, due_date)?;
+    }
+    for user in users {
+        p.assign_task(user, task_id)?;
+    }
+    // Set last so creating a task right in a done category doesn't repeat it already
+    if let Some(recurrence) = args.get_one::<Recurrence>("recur") {
+        p.set_task_recurrence(task_id, *recurrence)?;
+    }
+    println!("{}", task_id);
+    Ok(())
+}
+
+fn archive_tasks(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    match given_tasks(p, args)? {
+        Some(tasks) => {
+            for task in tasks {
+                let next = p.archive_task(task)?;
+                report_next_occurrence(p, task, next);
+            }
+            Ok(())
+        }
+        None => {
+            input::ensure_interactive("task ids")?;
+            prompt_archive_tasks(p)
+        }
+    }
+}
+
+fn unarchive_tasks(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let tasks = match given_tasks(p, args)? {
+        Some(tasks) => tasks,
+        None => {
+            input::ensure_interactive("task ids")?;
+            MultiSelect::new("Select tasks to restore:", get_archived_tasks_list(p))
+                .prompt()?
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        }
+    };
+    for task in tasks {
+        p.unarchive_task(task)?;
+    }
+    Ok(())
+}
+
+fn remove_tasks(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    match given_tasks(p, args)? {
+        Some(tasks) => {
+            for task in tasks {
+                for user in p.get_assigned_users(task)? {
+                    p.unassign_task(user.id, task)?;
+                }
+                p.remove_task(task);
+            }
+            Ok(())
+        }
+        None => {
+            input::ensure_interactive("task ids")?;
+            prompt_remove_tasks(p)
+        }
+    }
+}
+
+fn move_tasks(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let tasks = match given_tasks(p, args)? {
+        Some(tasks) => tasks,
+        None => {
+            input::ensure_interactive("task ids")?;
+            MultiSelect::new("Select tasks to move:", get_tasks_list_with_categories(p))
+                .prompt()?
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        }
+    };
+
+    let category = match args.get_one::<String>("to") {
+        Some(category) => p.resolve_category(category)?,
+        None => {
+            input::ensure_interactive("--to")?;
+            let categories = categories::get_categories_list(p, false);
+            Select::new("Select category:", categories).prompt()?.id
+        }
+    };
+
+    for task in tasks {
+        let next = p.move_task(task, category)?;
+        warn_if_blocked(p, task, category);
+        report_next_occurrence(p, task, next);
+    }
+    Ok(())
+}
+
+/// Checks out the task's branch, assigns the current user and moves the task to the in
+/// progress category.
+fn start_task(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let task_id = select_task(p, args, "Select task to start:")?;
+    let category = match args.get_one::<String>("to") {
+        Some(category) => p.resolve_category(category)?,
+        None => p.get_in_progress_category().ok_or_else(|| {
+            PiqoError::MissingInput("--to, the project has no in progress category".to_string())
+        })?,
+    };
+
+    if !args.get_flag("no-branch") {
+        let template = branches::branch_template(Path::new("."))?;
+        let branch = branches::branch_name(&template, p.get_task(task_id).unwrap());
+        if branches::switch_to(Path::new("."), &branch)? {
+            println!("Created branch {}", branch);
+        } else {
+            println!("Switched to branch {}", branch);
+        }
+        // An existing branch can hold a different version of the project
+        *p = data_storage::load_project()?;
+        let missing = match (p.get_task(task_id), p.get_category(category)) {
+            (None, _) => Some(format!("task {}", task_id)),
+            (_, None) => Some(format!("category {}", category)),
+            _ => None,
+        };
+        if let Some(missing) = missing {
+            return Err(PiqoError::InvalidInput(format!(
+                "switched to branch {} but its project has no {}, the task wasn't started",
+                branch, missing
+            )));
+        }
+    }
+    let user = utils::get_local_git_email().and_then(|email| p.get_user_by_email(&email));
+    match user {
+        Some(user) => p.assign_task(user.id, task_id)?,
+        None => eprintln!(
+            "{} you are not a user of the project, the task stays unassigned",
+            "Warning:".yellow()
+        ),
+    }
+    p.move_task(task_id, category)?;
+    warn_if_blocked(p, task_id, category);
+    println!(
+        "Started task {} in {}",
+        task_id,
+        p.get_category(category).unwrap().name
+    );
+    Ok(())
+}
+
+fn report_next_occurrence(p: &Project, task_id: u64, next: Option<u64>) {
+    let Some(due_date_utc) = next.and_then(|id| p.get_task(id)?.due_date_utc) else {
+        return;
+    };
+    let due = DateTime::from_timestamp(due_date_utc, 0)
+        .unwrap()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M");
+    println!(
+        "Task {} repeats, the next one is task {} due {}",
+        task_id,
+        next.unwrap(),
+        due
+    );
+}
+
+/// Finishing a task that others still block is allowed, but likely a mistake.
+fn warn_if_blocked(p: &Project, task_id: u64, category: u64) {
+    let Some(task) = p.get_task(task_id) else {
+        return;
+    };
+    if !p.is_done_category(category) || !p.is_blocked(task) {
+        return;
+    }
+    let blockers = p
+        .get_blockers(task_id)
+        .iter()
+        .filter(|b| !b.is_archived() && !p.is_done_category(b.category))
+        .map(|b| format!("{} {}", b.id, b.name))
+        .collect::<Vec<String>>();
+    eprintln!(
+        "{} task {} is still blocked by: {}",
+        "Warning:".yellow(),
+        task_id,
+        blockers.join(", ")
+    );
+}
+
+fn label_task(p: &mut Project, args: &ArgMatches, label: bool) -> Result<(), PiqoError> {
+    let task_id = select_task(p, args, "Select task:")?;
+    let Some(labels) = args.get_many::<String>("LABEL") else {
+        input::ensure_interactive("labels")?;
+        return prompt_select_labels(p, task_id);
+    };
+    let labels = labels
+        .map(|l| p.resolve_label(l))
+        .collect::<Result<Vec<u64>, _>>()?;
+    for label_id in labels {
+        match label {
+            true => p.label_task(task_id, label_id)?,
+            false => p.unlabel_task(task_id, label_id)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prompts with every label, the task's labels preselected, and applies the selection.
+fn prompt_select_labels(p: &mut Project, task_id: u64) -> Result<(), PiqoError> {
+    if p.labels.is_empty() {
+        println!("No labels yet, add some with `piqo labels add`");
+        return Ok(());
+    }
+    let task_labels = p.get_task(task_id).unwrap().labels.clone();
+    let tagged = p
+        .labels
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| task_labels.contains(&l.id))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let selected = MultiSelect::new("Select labels:", p.labels.clone())
+        .with_default(&tagged)
+        .prompt()?;
+    for label in p.labels.clone() {
+        match selected.iter().any(|s| s.id == label.id) {
+            true => p.label_task(task_id, label.id)?,
+            false => p.unlabel_task(task_id, label.id)?,
+        }
+    }
+    Ok(())
+}
+
+fn check_items(p: &mut Project, args: &ArgMatches, checked: bool) -> Result<(), PiqoError> {
+    let task_id = select_task(p, args, "Select task:")?;
+    match args.get_many::<u64>("INDEX") {
+        Some(indexes) => {
+            for index in indexes {
+                p.set_checklist_item_checked(task_id, *index, checked)?;
+            }
+            Ok(())
+        }
+        None => {
+            input::ensure_interactive("checklist indexes")?;
+            prompt_check_items(p, task_id)
+        }
+    }
+}
+
+fn promote_checklist_item(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let task_id = input::resolve_task(p, args.get_one::<String>("ID").unwrap())?;
+    let index = *args.get_one::<u64>("INDEX").unwrap();
+    println!("{}", p.promote_checklist_item(task_id, index)?);
+    Ok(())
+}
+
+fn link_tasks(p: &mut Project, args: &ArgMatches, link: bool) -> Result<(), PiqoError> {
+    let task = input::resolve_task(p, args.get_one::<String>("TASK").unwrap())?;
+    let other = input::resolve_task(p, args.get_one::<String>("OTHER").unwrap())?;
+    let (blocker, blocked) = match args.get_one::<String>("RELATION").unwrap().as_str() {
+        "blocks" => (task, other),
+        _ => (other, task),
+    };
+    match link {
+        true => p.link_tasks(blocker, blocked),
+        false => p.unlink_tasks(blocker, blocked),
+    }
+}
+
+pub(crate) fn assign_task(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    if args.get_one::<String>("ID").is_none() {
+        input::ensure_interactive("task id")?;
+        return users::prompt_assign_users(p);
+    }
+    let task_id = select_task(p, args, "Select task to assign")?;
+    match given_users(p, args)? {
+        Some(users) => {
+            for user in users {
+                p.assign_task(user, task_id)?;
+            }
+            Ok(())
+        }
+        None => {
+            input::ensure_interactive("users")?;
+            users::prompt_select_user_to_assign(p, task_id)
+        }
+    }
+}
+
+pub(crate) fn unassign_task(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    if args.get_one::<String>("ID").is_none() {
+        input::ensure_interactive("task id")?;
+        return users::prompt_unassign_users(p);
+    }
+    let task_id = select_task(p, args, "Select task to unassign")?;
+    let users = match given_users(p, args)? {
+        Some(users) => users,
+        None => {
+            input::ensure_interactive("users")?;
+            users::prompt_select_users_to_unassign(p, task_id)?
+        }
+    };
+    for user in users {
+        p.unassign_task(user, task_id)?;
+    }
+    Ok(())
+}
+
+fn edit_task(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let task_id = select_task(p, args, "Select task:")?;
+
+    let name = args.get_one::<String>("name");
+    let description = given_description(args)?;
+    let due_date = args
+        .get_one::<String>("due")
+        .map(|d| utils::parse_due_date(d))
+        .transpose()?;
+    let clear_due = args.get_flag("clear-due");
+    let parent = args
+        .get_one::<String>("parent")
+        .map(|t| input::resolve_task(p, t))
+        .transpose()?;
+    let clear_parent = args.get_flag("clear-parent");
+    let priority = args.get_one::<Priority>("priority");
+    let clear_priority = args.get_flag("clear-priority");
+    let estimate = args.get_one::<Estimate>("estimate");
+    let recurrence = args.get_one::<Recurrence>("recur");
+    let clear_recurrence = args.get_flag("clear-recurrence");
+    let clear_estimate = args.get_flag("clear-estimate");
+    let add_checks = args.get_many::<String>("add-check");
+    let remove_checks = args.get_many::<u64>("remove-check");
+    let rename_check = args
+        .get_many::<String>("rename-check")
+        .map(|v| v.collect::<Vec<_>>());
+    let rename_check = match rename_check.as_deref() {
+        Some([index, name]) => Some((
+            index.parse::<u64>().map_err(|_| {
+                PiqoError::InvalidInput(format!("invalid checklist index {}", index))
+            })?,
+            name.to_string(),
+        )),
+        _ => None,
+    };
+    let move_check = args
+        .get_many::<u64>("move-check")
+        .map(|v| v.copied().collect::<Vec<_>>());
+
+    let no_flags = name.is_none()
+        && description.is_none()
+        && due_date.is_none()
+        && !clear_due
+        && parent.is_none()
+        && !clear_parent
+        && priority.is_none()
+        && !clear_priority
+        && estimate.is_none()
+        && !clear_estimate
+        && recurrence.is_none()
+        && !clear_recurrence
+        && add_checks.is_none()
+        && remove_checks.is_none()
+        && rename_check.is_none()
+        && move_check.is_none();
+    if no_flags {
+        input::ensure_interactive("fields to edit")?;
+        return prompt_edit_task(p, task_id);
+    }
+
+    if let Some(name) = name {
+        p.edit_task_name(task_id, name.to_owned())?;
+    }
+    if let Some(description) = description {
+        p.edit_task_description(task_id, description)?;
+    }
+    if let Some(due_date) = due_date {
+        p.set_task_due_date(task_id, due_date)?;
+    }
+    if clear_due {
+        p.clear_task_due_date(task_id)?;
+    }
+    if parent.is_some() || clear_parent {
+        p.set_parent(task_id, parent)?;
+    }
+    if let Some(priority) = priority {
+        p.set_task_priority(task_id, *priority)?;
+    }
+    if clear_priority {
+        p.clear_task_priority(task_id)?;
+    }
+    if let Some(estimate) = estimate {
+        p.set_task_estimate(task_id, *estimate)?;
+    }
+    if clear_estimate {
+        p.clear_task_estimate(task_id)?;
+    }
+    if let Some(recurrence) = recurrence {
+        p.set_task_recurrence(task_id, *recurrence)?;
+    }
+    if clear_recurrence {
+        p.clear_task_recurrence(task_id)?;
+    }
+    for item in add_checks.into_iter().flatten() {
+        p.add_checklist_item(task_id, item.to_owned())?;
+    }
+    if let Some((index, name)) = rename_check {
+        p.rename_checklist_item(task_id, index, name)?;
+    }
+    if let Some([index, position]) = move_check.as_deref() {
+        p.move_checklist_item(task_id, *index, *position as usize)?;
+    }
+    for index in remove_checks.into_iter().flatten() {
+        p.remove_checklist_item(task_id, *index)?;
+    }
     Ok(())
 }
 
-fn prompt_remove_tasks(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+fn prompt_remove_tasks(p: &mut Project) -> Result<(), PiqoError> {
     let selected_tasks = MultiSelect::new("Select tasks to remove:", get_tasks_list(p)).prompt()?;
 
     for task in selected_tasks {
-        let assigned_users = p.get_assigned_users(task.id);
+        let assigned_users = p.get_assigned_users(task.id)?;
         let remove =
             inquire::Confirm::new(&format!("Confirm deletion of task {}", task.name)).prompt()?;
 
@@ -46,7 +869,7 @@ fn prompt_remove_tasks(p: &mut Project) -> Result<(), inquire::error::InquireErr
         }
 
         for user in assigned_users {
-            p.unassign_task(user.id, task.id);
+            p.unassign_task(user.id, task.id)?;
         }
         p.remove_task(task.id);
     }
@@ -54,8 +877,9 @@ fn prompt_remove_tasks(p: &mut Project) -> Result<(), inquire::error::InquireErr
     Ok(())
 }
 
+/// Tasks to pick from in prompts, archived tasks are left out.
 pub(crate) fn get_tasks_list(p: &Project) -> Vec<TaskItem> {
-    p.tasks
+    p.get_unarchived_tasks()
         .iter()
         .map(|t| TaskItem {
             id: t.id,
@@ -64,33 +888,29 @@ pub(crate) fn get_tasks_list(p: &Project) -> Vec<TaskItem> {
         })
         .collect::<Vec<TaskItem>>()
 }
-pub(crate) fn get_tasks_list_with_categories(p: &Project) -> Vec<TaskItem> {
-    p.tasks
+fn get_archived_tasks_list(p: &Project) -> Vec<TaskItem> {
+    p.get_archived_tasks()
         .iter()
         .map(|t| TaskItem {
             id: t.id,
             name: t.name.to_owned(),
-            category: p.get_category_name(t.category),
+            category: None,
         })
         .collect::<Vec<TaskItem>>()
 }
 
-fn prompt_move_tasks(p: &mut Project) -> Result<(), inquire::error::InquireError> {
-    let selected_tasks =
-        MultiSelect::new("Select tasks to move:", get_tasks_list_with_categories(p)).prompt()?;
-
-    let categories = categories::get_categories_list(p, false);
-
-    let selected_category = Select::new("Select category:", categories).prompt()?;
-
-    for task in selected_tasks {
-        p.move_task(task.id, selected_category.id);
-    }
-
-    Ok(())
+pub(crate) fn get_tasks_list_with_categories(p: &Project) -> Vec<TaskItem> {
+    p.get_unarchived_tasks()
+        .iter()
+        .map(|t| TaskItem {
+            id: t.id,
+            name: t.name.to_owned(),
+            category: p.get_category_name(t.category),
+        })
+        .collect::<Vec<TaskItem>>()
 }
 
-fn prompt_get_due_time() -> Result<i64, inquire::error::InquireError> {
+fn prompt_get_due_time() -> Result<i64, PiqoError> {
     let custom_date = DateSelect::new("Due date:")
         .with_help_message("When is the task due?")
         .prompt()?;
@@ -148,61 +968,106 @@ fn prompt_get_due_time() -> Result<i64, inquire::error::InquireError> {
     Ok(unix_time_utc)
 }
 
-fn prompt_create_checklist_item(
-    p: &mut Project,
-    task_id: u64,
-) -> Result<(), inquire::error::InquireError> {
+fn prompt_create_checklist_item(p: &mut Project, task_id: u64) -> Result<(), PiqoError> {
     let name = inquire::Text::new("Checklist item name:")
         .with_help_message("What is the name of the checklist item?")
         .prompt()?;
 
-    p.add_checklist_item(task_id, name);
+    p.add_checklist_item(task_id, name)?;
     while inquire::Confirm::new("Add another checklist item?").prompt()? {
         let name = inquire::Text::new("Checklist item name:")
             .with_help_message("What is the name of the checklist item?")
             .prompt()?;
-        p.add_checklist_item(task_id, name);
+        p.add_checklist_item(task_id, name)?;
     }
     Ok(())
 }
 
-fn prompt_edit_task(p: &mut Project) -> Result<(), inquire::error::InquireError> {
-    let selected_task = Select::new("Select task:", get_tasks_list(p))
-        .prompt()
-        .unwrap();
+/// Prompts with every checklist item, the checked ones preselected, and applies the selection.
+fn prompt_check_items(p: &mut Project, task_id: u64) -> Result<(), PiqoError> {
+    let checklist_items = p.get_task_checklist(task_id)?;
+    let checked = checklist_items
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.checked)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let selected = MultiSelect::new("Select checked items:", checklist_items.clone())
+        .with_default(&checked)
+        .prompt()?;
+    for item in checklist_items {
+        let checked = selected.iter().any(|s| s.index == item.index);
+        p.set_checklist_item_checked(task_id, item.index, checked)?;
+    }
+    Ok(())
+}
 
-    let mut fields = vec!["Name", "Description", "Checklist", "Set due date"];
+fn prompt_edit_task(p: &mut Project, task_id: u64) -> Result<(), PiqoError> {
+    let mut fields = vec![
+        "Name",
+        "Description",
+        "Checklist",
+        "Set due date",
+        "Priority",
+        "Labels",
+        "Estimate",
+        "Repeat",
+    ];
 
-    let task_due_time = p.get_task_due_time(selected_task.id);
+    let task_due_time = p.get_task_due_time(task_id)?;
     if task_due_time.is_some() {
         fields.push("Clear due date");
     }
 
-    let selected_field = Select::new("Select field:", fields).prompt().unwrap();
+    let selected_field = Select::new("Select field:", fields).prompt()?;
     match selected_field {
         "Name" => {
             let new_name = inquire::Text::new("New name:").prompt()?;
-            p.edit_task_name(selected_task.id, new_name);
+            p.edit_task_name(task_id, new_name)?;
         }
         "Description" => {
             let new_description = inquire::Editor::new("New description:")
-                .with_predefined_text(p.get_task_description(selected_task.id))
+                .with_predefined_text(p.get_task_description(task_id)?)
                 .prompt()?;
-            p.edit_task_description(selected_task.id, new_description);
+            p.edit_task_description(task_id, new_description)?;
         }
         "Checklist" => {
-            let checklist_option = Select::new(
-                "Select checklist item:",
-                vec!["Add checklist item", "Remove checklist item"],
-            )
-                .prompt()?;
+            let mut checklist_options = vec!["Add checklist item"];
+            if !p.get_task_checklist(task_id)?.is_empty() {
+                checklist_options.extend([
+                    "Check items",
+                    "Rename checklist item",
+                    "Move checklist item",
+                    "Remove checklist item",
+                ]);
+            }
+            let checklist_option =
+                Select::new("Select checklist item:", checklist_options).prompt()?;
             match checklist_option {
-                "Add checklist item" => prompt_create_checklist_item(p, selected_task.id)?,
+                "Add checklist item" => prompt_create_checklist_item(p, task_id)?,
+                "Check items" => prompt_check_items(p, task_id)?,
+                "Rename checklist item" => {
+                    let checklist_items = p.get_task_checklist(task_id)?;
+                    let item = Select::new("Select checklist item:", checklist_items).prompt()?;
+                    let name = inquire::Text::new("New name:")
+                        .with_initial_value(&item.name)
+                        .prompt()?;
+                    p.rename_checklist_item(task_id, item.index, name)?;
+                }
+                "Move checklist item" => {
+                    let checklist_items = p.get_task_checklist(task_id)?;
+                    let count = checklist_items.len();
+                    let item = Select::new("Select checklist item:", checklist_items).prompt()?;
+                    let position = CustomType::<usize>::new("New position:")
+                        .with_help_message(&format!("1 to {}", count))
+                        .prompt()?;
+                    p.move_checklist_item(task_id, item.index, position)?;
+                }
                 "Remove checklist item" => {
-                    let checklist_items = p.get_task_checklist(selected_task.id);
+                    let checklist_items = p.get_task_checklist(task_id)?;
                     let selected_checklist_item =
                         Select::new("Select checklist item:", checklist_items).prompt()?;
-                    p.remove_checklist_item(selected_task.id, selected_checklist_item.index);
+                    p.remove_checklist_item(task_id, selected_checklist_item.index)?;
                 }
                 _ => unreachable!(
                     "Exhausted list of subcommands and subcommand_required prevents `None`"
@@ -210,9 +1075,31 @@ fn prompt_edit_task(p: &mut Project) -> Result<(), inquire::error::InquireError>
             }
         }
 
+        "Labels" => prompt_select_labels(p, task_id)?,
+        "Repeat" => {
+            let recurrence = CustomType::<Recurrence>::new("Repeat:")
+                .with_help_message("daily, weekly, monthly, yearly or e.g. every 2 weeks")
+                .prompt()?;
+            p.set_task_recurrence(task_id, recurrence)?;
+        }
+        "Estimate" => {
+            let estimate = CustomType::<Estimate>::new("Estimate:")
+                .with_help_message("time like 4h or 1h30m, or story points like 3pts")
+                .prompt()?;
+            p.set_task_estimate(task_id, estimate)?;
+        }
+        "Priority" => {
+            let mut options = Priority::ALL.map(|p| p.to_string()).to_vec();
+            options.push("None".to_string());
+            let selected = Select::new("Priority:", options).prompt()?;
+            match selected.parse::<Priority>() {
+                Ok(priority) => p.set_task_priority(task_id, priority)?,
+                Err(_) => p.clear_task_priority(task_id)?,
+            }
+        }
         "Set due date" => {
             let due_date = prompt_get_due_time()?;
-            p.set_task_due_date(selected_task.id, due_date);
+            p.set_task_due_date(task_id, due_date)?;
         }
         "Clear due date" => {
             let due_date = DateTime::from_timestamp(task_due_time.unwrap(), 0)
@@ -221,7 +1108,7 @@ fn prompt_edit_task(p: &mut Project) -> Result<(), inquire::error::InquireError>
             let clear =
                 inquire::Confirm::new(&format!("Clear due date ({})?", due_date)).prompt()?;
             if clear {
-                p.clear_task_due_date(selected_task.id);
+                p.clear_task_due_date(task_id)?;
             }
         }
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
@@ -229,25 +1116,26 @@ fn prompt_edit_task(p: &mut Project) -> Result<(), inquire::error::InquireError>
     Ok(())
 }
 
-fn prompt_archive_tasks(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+fn prompt_archive_tasks(p: &mut Project) -> Result<(), PiqoError> {
     let selected_tasks =
         MultiSelect::new("Select tasks to archive:", get_tasks_list(p)).prompt()?;
 
     for task in selected_tasks {
-        p.archive_task(task.id);
+        let next = p.archive_task(task.id)?;
+        report_next_occurrence(p, task.id, next);
     }
 
     Ok(())
 }
 
-fn prompt_create_task(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+fn prompt_create_task(p: &mut Project) -> Result<(), PiqoError> {
     let name = inquire::Text::new("Name:").prompt()?;
     let task_id = p.add_task(name);
 
     users::prompt_select_user_to_assign(p, task_id)
 }
 
-pub(crate) fn prompt_create_tasks(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_create_tasks(p: &mut Project) -> Result<(), PiqoError> {
     //TODO: use inquire::Confirm instead
     prompt_create_task(p)?;
     loop {
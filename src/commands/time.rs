@@ -0,0 +1,108 @@
+use clap::{Arg, ArgMatches, Command};
+use piqo::{PiqoError, TimeGrouping, data_storage, utils};
+
+use super::{input, tasks};
+
+pub(crate) fn start_command() -> Command {
+    Command::new("start")
+        .about("Starts tracking your time on a task, stopping the running timer")
+        .arg(Arg::new("ID").help("id of the task, prompts when omitted"))
+}
+
+pub(crate) fn stop_command() -> Command {
+    Command::new("stop").about("Stops tracking your time")
+}
+
+pub(crate) fn log_time_command() -> Command {
+    Command::new("log-time")
+        .about("Logs time you spent on a task")
+        .arg(Arg::new("ID").required(true).help("id of the task"))
+        .arg(
+            Arg::new("TIME")
+                .required(true)
+                .help("time spent, e.g. 1h30m, 2h or 45m"),
+        )
+}
+
+pub(crate) fn report_command() -> Command {
+    Command::new("report")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Summarizes the project")
+        .subcommand(
+            Command::new("time")
+                .about("Sums up the logged time")
+                .arg(
+                    Arg::new("since").long("since").help(
+                        "only count time after a date or a duration ago, e.g. 2026-11-01 or 2w",
+                    ),
+                )
+                .arg(
+                    Arg::new("by")
+                        .long("by")
+                        .value_parser(["user", "task"])
+                        .default_value("user")
+                        .help("what to sum the time up by"),
+                ),
+        )
+}
+
+pub(crate) fn start(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+    let task_id = tasks::select_task(&p, matches, "Select task to work on:")?;
+    if let Some(stopped) = p.start_timer(task_id)? {
+        let now = chrono::Utc::now().timestamp();
+        println!(
+            "Stopped task {} after {}",
+            stopped.task_id,
+            utils::format_time_spent(stopped.seconds(now))
+        );
+    }
+    println!("Started tracking time on task {}", task_id);
+    super::store(&p)
+}
+
+pub(crate) fn stop() -> Result<(), PiqoError> {
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+    let Some(stopped) = p.stop_timer()? else {
+        println!("No timer running");
+        return Ok(());
+    };
+    let now = chrono::Utc::now().timestamp();
+    println!(
+        "Stopped task {} after {}",
+        stopped.task_id,
+        utils::format_time_spent(stopped.seconds(now))
+    );
+    super::store(&p)
+}
+
+pub(crate) fn log_time(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+    let task_id = tasks::select_task(&p, matches, "Select task:")?;
+    let seconds = utils::parse_time_spent(matches.get_one::<String>("TIME").unwrap())?;
+    p.log_time(task_id, seconds)?;
+    super::store(&p)
+}
+
+pub(crate) fn report(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let p = data_storage::load_project()?;
+    match matches.subcommand() {
+        Some(("time", args)) => {
+            let since = match args.get_one::<String>("since") {
+                Some(since) => input::parse_since(since)?,
+                None => 0,
+            };
+            let grouping = match args.get_one::<String>("by").unwrap().as_str() {
+                "task" => TimeGrouping::Task,
+                _ => TimeGrouping::User,
+            };
+            p.print_time_report(since, grouping);
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use piqo::tasks_csv::{self, ImportedRow};
+use piqo::{PiqoError, data_storage};
+
+use super::{filter, input};
+
+pub(crate) fn export_command() -> Command {
+    Command::new("export")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Exports tasks for other tools")
+        .subcommand(
+            Command::new("csv")
+                .about("Exports tasks as CSV, archived tasks only with --all or --archived")
+                .args(filter::args())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("file to write to, stdout when omitted"),
+                ),
+        )
+}
+
+pub(crate) fn import_command() -> Command {
+    Command::new("import")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Imports tasks from other tools")
+        .subcommand(
+            Command::new("csv")
+                .about("Creates and updates tasks from CSV, rows with the id of a task update it")
+                .arg(
+                    Arg::new("FILE")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("CSV file to import, - reads stdin"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("only print the tasks that would be created or updated"),
+                )
+                .arg(
+                    Arg::new("create-missing")
+                        .long("create-missing")
+                        .action(ArgAction::SetTrue)
+                        .help("creates unknown categories, and users given by email"),
+                ),
+        )
+}
+
+pub(crate) fn export(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(("csv", args)) = matches.subcommand() else {
+        unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`")
+    };
+    let p = data_storage::load_project()?;
+    let tasks = p.query(&filter::query(args)?)?;
+    match args.get_one::<PathBuf>("output") {
+        Some(path) => tasks_csv::export(&p, &tasks, File::create(path)?),
+        None => tasks_csv::export(&p, &tasks, io::stdout().lock()),
+    }
+}
+
+pub(crate) fn import(matches: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(("csv", args)) = matches.subcommand() else {
+        unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`")
+    };
+    let _lock = data_storage::lock_project()?;
+    let mut p = data_storage::load_project()?;
+
+    let text = input::read_text_file(args.get_one::<PathBuf>("FILE").unwrap())?;
+    let dry_run = args.get_flag("dry-run");
+    let rows = tasks_csv::import(
+        &mut p,
+        text.as_bytes(),
+        args.get_flag("create-missing"),
+        dry_run,
+    )?;
+
+    let (mut created, mut updated) = (0, 0);
+    for row in &rows {
+        match row {
+            ImportedRow::Created { line, task_id } => {
+                created += 1;
+                let name = &p.get_task(*task_id).unwrap().name;
+                match dry_run {
+                    true => println!("Would create {} (line {})", name, line),
+                    false => println!("Created task {} {} (line {})", task_id, name, line),
+                }
+            }
+            ImportedRow::Updated {
+                line,
+                task_id,
+                columns,
+            } => {
+                updated += 1;
+                let verb = if dry_run { "Would update" } else { "Updated" };
+                println!(
+                    "{} task {}: {} (line {})",
+                    verb,
+                    task_id,
+                    columns.join(", "),
+                    line
+                );
+            }
+            ImportedRow::Unchanged { .. } => {}
+        }
+    }
+    println!(
+        "{} created, {} updated, {} unchanged",
+        created,
+        updated,
+        rows.len() - created - updated
+    );
+
+    if !dry_run {
+        super::store(&p)?;
+    }
+    Ok(())
+}
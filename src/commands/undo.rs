@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use piqo::{Journal, JournalEntry, PiqoError, data_storage, journal};
+
+pub(crate) fn undo_command() -> Command {
+    Command::new("undo")
+        .about("Reverts the last piqo commands that changed the project")
+        .arg(count_arg())
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .action(ArgAction::SetTrue)
+                .help("show what would be undone, most recent first, everything without COUNT"),
+        )
+}
+
+pub(crate) fn redo_command() -> Command {
+    Command::new("redo")
+        .about("Replays commands reverted by undo")
+        .arg(count_arg())
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .action(ArgAction::SetTrue)
+                .help("show what would be redone, most recent first, everything without COUNT"),
+        )
+}
+
+fn count_arg() -> Arg {
+    Arg::new("COUNT")
+        .value_parser(value_parser!(usize))
+        .default_value("1")
+        .help("number of commands")
+}
+
+pub(crate) fn undo(matches: &ArgMatches) -> Result<(), PiqoError> {
+    step(matches, true)
+}
+
+pub(crate) fn redo(matches: &ArgMatches) -> Result<(), PiqoError> {
+    step(matches, false)
+}
+
+/// Undoes or redoes the commands on top of the journal's undo or redo stack.
+fn step(matches: &ArgMatches, undo: bool) -> Result<(), PiqoError> {
+    let path = journal::journal_path(Path::new("."))?;
+    let _lock = data_storage::lock_project()?;
+    let mut journal = Journal::load(&path)?;
+    let (entries, verb) = match undo {
+        true => (&journal.undo, "undo"),
+        false => (&journal.redo, "redo"),
+    };
+    if entries.is_empty() {
+        println!("Nothing to {}", verb);
+        return Ok(());
+    }
+
+    let count = *matches.get_one::<usize>("COUNT").unwrap();
+    let stepped = entries
+        .iter()
+        .rev()
+        .take(count)
+        .cloned()
+        .collect::<Vec<JournalEntry>>();
+    if matches.get_flag("list") {
+        match matches.value_source("COUNT") {
+            Some(ValueSource::CommandLine) => print_entries(&stepped),
+            _ => print_entries(&entries.iter().rev().cloned().collect::<Vec<JournalEntry>>()),
+        }
+        return Ok(());
+    }
+
+    let mut p = data_storage::load_project()?;
+    if undo {
+        journal.undo(&mut p, count)?;
+    } else {
+        journal.redo(&mut p, count)?;
+    }
+    data_storage::store_project(&p)?;
+    journal.store(&path)?;
+
+    for entry in stepped {
+        let verb = if undo { "Undid" } else { "Redid" };
+        println!("{} {}", verb, entry.command);
+    }
+    Ok(())
+}
+
+/// Prints the entries, given most recent first.
+fn print_entries(entries: &[JournalEntry]) {
+    for (i, entry) in entries.iter().enumerate() {
+        println!(
+            "{:>3}  {}  {}",
+            i + 1,
+            format_time(entry.at_utc),
+            entry.command
+        );
+    }
+}
+
+fn format_time(at_utc: i64) -> String {
+    DateTime::from_timestamp(at_utc, 0)
+        .unwrap()
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
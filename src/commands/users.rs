@@ -1,54 +1,217 @@
-use std::collections::HashMap;
+use std::path::Path;
 
-use clap::ArgMatches;
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use inquire::{MultiSelect, Select};
 use owo_colors::OwoColorize;
 
-use crate::{
-    data_storage,
-    models::{Project, User},
-};
+use piqo::{PiqoError, Project, User, authors, data_storage};
+
+use super::{input, list_items::UserItem, tasks};
+
+pub(crate) fn command() -> Command {
+    Command::new("users")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .about("Alter users of project")
+        .subcommand(
+            Command::new("add")
+                .about("Adds new users, prompts when no name is given")
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .help("name of the user"),
+                )
+                .arg(
+                    Arg::new("email")
+                        .short('e')
+                        .long("email")
+                        .help("git email of the user"),
+                )
+                .arg(
+                    Arg::new("from-git")
+                        .long("from-git")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "email"])
+                        .help("picks users among the commit authors who aren't users yet"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .requires("from-git")
+                        .help("only authors of commits after a date or a duration ago, e.g. 2026-01-01 or 12w"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .requires("from-git")
+                        .help("adds all the authors without prompting"),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Removes users")
+                .arg(users_arg().num_args(1..)),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Edits users, prompts for the field to edit when no flags are given")
+                .arg(users_arg())
+                .arg(Arg::new("name").short('n').long("name").help("new name"))
+                .arg(
+                    Arg::new("email")
+                        .short('e')
+                        .long("email")
+                        .help("new git email, an empty string removes it"),
+                ),
+        )
+        .subcommand(tasks::assign_command("assign", "Assign users to tasks"))
+        .subcommand(tasks::assign_command(
+            "unassign",
+            "Unassign users from tasks",
+        ))
+        .subcommand(Command::new("list").about("Lists users"))
+        .subcommand(
+            Command::new("print")
+                .about("Prints details of one user")
+                .arg(users_arg()),
+        )
+}
 
-use super::{list_items::UserItem, tasks};
+fn users_arg() -> Arg {
+    Arg::new("USER").help("email, name or id of the user, prompts when omitted")
+}
 
-pub(crate) fn prompt_users(sub_matches: &ArgMatches) -> Result<(), inquire::error::InquireError> {
-    let mut p = data_storage::load_project().unwrap();
+pub(crate) fn prompt_users(sub_matches: &ArgMatches) -> Result<(), PiqoError> {
+    let lock = super::lock_unless_read_only(sub_matches)?;
+    let mut p = data_storage::load_project()?;
 
     match sub_matches.subcommand() {
-        Some(("add", _)) => prompt_add_users(&mut p)?,
-        Some(("remove", _)) => prompt_remove_users(&mut p)?,
-        Some(("edit", _)) => prompt_edit_user(&mut p)?,
-        Some(("assign", _)) => prompt_assign_users(&mut p)?,
-        Some(("unassign", _)) => prompt_unassign_users(&mut p)?,
+        Some(("add", args)) => add_user(&mut p, args)?,
+        Some(("remove", args)) => remove_users(&mut p, args)?,
+        Some(("edit", args)) => edit_user(&mut p, args)?,
+        Some(("assign", args)) => tasks::assign_task(&mut p, args)?,
+        Some(("unassign", args)) => tasks::unassign_task(&mut p, args)?,
         Some(("list", _)) => p.print_users(),
         Some(("print", args)) => {
-            let id: u64 = args.get_one::<String>("ID").unwrap().parse().unwrap();
+            let id = select_user(&p, args, "Select user:")?;
             print_single_user(&p, id);
         }
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
     };
-    data_storage::store_project(&p)?;
+    if lock.is_some() {
+        super::store(&p)?;
+    }
+    Ok(())
+}
+
+/// Returns the user given as `USER` or prompts for one.
+fn select_user(p: &Project, args: &ArgMatches, msg: &str) -> Result<u64, PiqoError> {
+    match args.get_one::<String>("USER") {
+        Some(user) => p.resolve_user(user),
+        None => {
+            input::ensure_interactive("user")?;
+            Ok(Select::new(msg, get_users_mod_list(p)).prompt()?.id)
+        }
+    }
+}
+
+fn add_user(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    if args.get_flag("from-git") {
+        let since = args
+            .get_one::<String>("since")
+            .map(|s| input::parse_since(s))
+            .transpose()?;
+        if args.get_flag("all") {
+            return add_authors(p, since);
+        }
+        input::ensure_interactive("--all")?;
+        return prompt_add_authors(p, since);
+    }
+    let Some(name) = args.get_one::<String>("name") else {
+        input::ensure_interactive("--name")?;
+        return prompt_add_users(p);
+    };
+    let email = match args.get_one::<String>("email") {
+        Some(email) => email.to_owned(),
+        None => {
+            input::ensure_interactive("--email")?;
+            inquire::Text::new("Email:").prompt()?
+        }
+    };
+    if p.get_user_by_email(&email).is_some() {
+        return Err(PiqoError::InvalidInput(format!(
+            "user with email {} already exists",
+            email
+        )));
+    }
+    p.add_user(name, &email);
     Ok(())
 }
 
-fn prompt_edit_user(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+fn remove_users(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let Some(users) = args.get_many::<String>("USER") else {
+        input::ensure_interactive("users")?;
+        return prompt_remove_users(p);
+    };
+    let users = users
+        .map(|u| p.resolve_user(u))
+        .collect::<Result<Vec<_>, _>>()?;
+    for user in users {
+        let user = p.get_user(user).unwrap();
+        p.remove_user(&user);
+    }
+    Ok(())
+}
+
+fn edit_user(p: &mut Project, args: &ArgMatches) -> Result<(), PiqoError> {
+    let name = args.get_one::<String>("name");
+    let email = args.get_one::<String>("email");
+    if name.is_none() && email.is_none() {
+        input::ensure_interactive("fields to edit")?;
+        if args.get_one::<String>("USER").is_none() {
+            return prompt_edit_user(p);
+        }
+    }
+
+    let user = p.get_user(select_user(p, args, "Select user:")?).unwrap();
+    if name.is_none() && email.is_none() {
+        return prompt_edit_user_fields(p, user);
+    }
+
+    let name = name.unwrap_or(&user.name);
+    let email = match email {
+        Some(email) if email.is_empty() => None,
+        Some(email) => Some(email.to_owned()),
+        None => user.git_email,
+    };
+    p.edit_user(user.id, name, email)?;
+    Ok(())
+}
+
+fn prompt_edit_user(p: &mut Project) -> Result<(), PiqoError> {
     let users = get_users_mod_list(p);
     let selected = Select::new("Select user:", users).prompt()?;
+    prompt_edit_user_fields(p, selected)
+}
+
+fn prompt_edit_user_fields(p: &mut Project, selected: User) -> Result<(), PiqoError> {
     let selected_edit = Select::new("Select field to edit:", vec!["Name", "Email"]).prompt()?;
     match selected_edit {
         "Name" => {
             let new_name = inquire::Text::new("New name").prompt()?;
             if new_name.is_empty() {
-                return Err(inquire::error::InquireError::OperationCanceled);
+                return Err(inquire::InquireError::OperationCanceled.into());
             }
-            p.edit_user(selected.id, new_name.as_str(), selected.git_email);
+            p.edit_user(selected.id, new_name.as_str(), selected.git_email)?;
         }
         "Email" => {
             let new_git_email = inquire::Text::new("New git email").prompt()?;
             if new_git_email.is_empty() {
-                p.edit_user(selected.id, selected.name.as_str(), None);
+                p.edit_user(selected.id, selected.name.as_str(), None)?;
             } else {
-                p.edit_user(selected.id, selected.name.as_str(), Some(new_git_email));
+                p.edit_user(selected.id, selected.name.as_str(), Some(new_git_email))?;
             }
         }
         _ => unreachable!(),
@@ -56,12 +219,24 @@ fn prompt_edit_user(p: &mut Project) -> Result<(), inquire::error::InquireError>
     Ok(())
 }
 
-//TODO: Fix this
 fn print_single_user(p: &Project, id: u64) {
-    match p.get_user(id) {
-        Some(user) => println!("{:?}", user),
-        None => println!("User not found"),
+    let Some(user) = p.get_user(id) else {
+        println!("User not found");
+        return;
+    };
+    match &user.git_email {
+        Some(email) => println!("{} <{}>", user.name.bold(), email),
+        None => println!("{} <No email>", user.name.bold()),
     }
+    println!("Id: {}", user.id);
+    println!();
+    let mut tasks = p
+        .get_tasks_assigned_to(id)
+        .into_iter()
+        .filter(|t| !t.is_archived())
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.category));
+    p.print_user_status(&tasks);
 }
 
 pub(crate) fn get_users_mod_list(p: &Project) -> Vec<User> {
@@ -75,26 +250,17 @@ pub(crate) fn get_users_mod_list(p: &Project) -> Vec<User> {
         .collect()
 }
 
-fn get_users_assigned_mod_list(p: &Project, task_id: u64) -> Vec<User> {
+fn get_users_assigned_mod_list(p: &Project, task_id: u64) -> Result<Vec<User>, PiqoError> {
     p.get_assigned_users(task_id)
-        .iter()
-        .map(|u| User {
-            id: u.id,
-            name: u.name.to_string(),
-            git_email: u.git_email.to_owned(),
-        })
-        .collect()
 }
 
-pub(crate) fn prompt_select_user_to_assign(
-    p: &mut Project,
-    task_id: u64,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_select_user_to_assign(p: &mut Project, task_id: u64) -> Result<(), PiqoError> {
     let users = get_users_mod_list(p);
+    let assigned = p.get_assigned_users(task_id)?;
 
     let users = users
         .iter()
-        .filter(|u| !p.get_assigned_users(task_id).iter().any(|a| a.id == u.id))
+        .filter(|u| !assigned.iter().any(|a| a.id == u.id))
         .collect::<Vec<&User>>();
 
     if users.is_empty() {
@@ -105,11 +271,11 @@ pub(crate) fn prompt_select_user_to_assign(
     let users_to_assign = MultiSelect::new("Select users to assign", users).prompt()?;
 
     for user in users_to_assign {
-        p.assign_task(user.id, task_id);
+        p.assign_task(user.id, task_id)?;
     }
     Ok(())
 }
-pub(crate) fn prompt_assign_users(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_assign_users(p: &mut Project) -> Result<(), PiqoError> {
     let tasks_mod_list = tasks::get_tasks_list(p);
     if tasks_mod_list.is_empty() {
         let msg = "No tasks to assign".red();
@@ -122,7 +288,7 @@ pub(crate) fn prompt_assign_users(p: &mut Project) -> Result<(), inquire::error:
     prompt_select_user_to_assign(p, selected_task.id)
 }
 
-pub(crate) fn prompt_unassign_users(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_unassign_users(p: &mut Project) -> Result<(), PiqoError> {
     let tasks_mod_list = tasks::get_tasks_list(p);
 
     if tasks_mod_list.is_empty() {
@@ -132,73 +298,78 @@ pub(crate) fn prompt_unassign_users(p: &mut Project) -> Result<(), inquire::erro
 
     let selected_task = Select::new("Select task to unassign", tasks_mod_list).prompt()?;
 
-    let assigned = get_users_assigned_mod_list(p, selected_task.id);
+    for user in prompt_select_users_to_unassign(p, selected_task.id)? {
+        p.unassign_task(user, selected_task.id)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn prompt_select_users_to_unassign(
+    p: &Project,
+    task_id: u64,
+) -> Result<Vec<u64>, PiqoError> {
+    let assigned = get_users_assigned_mod_list(p, task_id)?;
 
     if assigned.is_empty() {
         println!("No users assigned to this task");
-        return Ok(());
+        return Ok(vec![]);
     }
 
     let users_to_unassign = MultiSelect::new("Select users to unassign", assigned).prompt()?;
-
-    for user in users_to_unassign {
-        p.unassign_task(user.id, selected_task.id);
-    }
-    Ok(())
+    Ok(users_to_unassign.iter().map(|u| u.id).collect())
 }
 
-pub(crate) fn prompt_add_users(
-    p: &mut crate::models::Project,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_add_users(p: &mut Project) -> Result<(), PiqoError> {
     let option_a = "Scrape git users";
     let option_b = "Add user manually";
     let selections = vec![option_a, option_b];
 
     let choice = Select::new("Choose:", selections).prompt()?;
     match choice {
-        "Scrape git users" => {
-            let output = std::process::Command::new("git")
-                .arg("log")
-                .arg("--format=\"%an | %aE\" | sort -u")
-                .output()?;
-
-            let list = String::from_utf8(output.stdout);
-
-            let mut unique_dict = HashMap::new();
-            list.unwrap().lines().for_each(|l| {
-                unique_dict.insert(
-                    l.split(" | ").collect::<Vec<&str>>()[1].to_string(),
-                    l.split(" | ").collect::<Vec<&str>>()[0].to_string(),
-                );
-            });
-
-            let split_one = unique_dict
-                .iter()
-                .map(|(email, name)| UserItem {
-                    name: name.trim().replace('\"', "").to_string(),
-                    git_email: email.trim().replace('\"', "").to_string(),
-                })
-                .collect::<Vec<UserItem>>();
-
-            let choices = MultiSelect::new("Select users to add", split_one).prompt()?;
-
-            for ele in choices {
-                p.add_user(&ele.name, &ele.git_email);
-            }
-
-            data_storage::store_project(p)?;
-            Ok(())
-        }
+        "Scrape git users" => prompt_add_authors(p, None),
         "Add user manually" => {
             prompt_create_users_manually(p)?;
-
-            data_storage::store_project(p)?;
             Ok(())
         }
         _ => unreachable!("Exhausted list of options and arg_required_else_help prevents `None`"),
     }
 }
-fn prompt_remove_users(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+
+/// Commit authors who aren't users yet, most commits first.
+fn discover_authors(p: &Project, since: Option<i64>) -> Result<Vec<UserItem>, PiqoError> {
+    let authors = authors::authors(Path::new("."), since)?;
+    Ok(authors::new_authors(p, authors)
+        .into_iter()
+        .map(|a| UserItem {
+            name: a.name,
+            git_email: a.email,
+            commits: a.commits,
+        })
+        .collect())
+}
+
+fn add_authors(p: &mut Project, since: Option<i64>) -> Result<(), PiqoError> {
+    for author in discover_authors(p, since)? {
+        p.add_user(&author.name, &author.git_email);
+        println!("Added {} <{}>", author.name, author.git_email);
+    }
+    Ok(())
+}
+
+fn prompt_add_authors(p: &mut Project, since: Option<i64>) -> Result<(), PiqoError> {
+    let authors = discover_authors(p, since)?;
+    if authors.is_empty() {
+        println!("No commit authors to add, they are all users already");
+        return Ok(());
+    }
+    let choices = MultiSelect::new("Select users to add", authors).prompt()?;
+    for author in choices {
+        p.add_user(&author.name, &author.git_email);
+    }
+    Ok(())
+}
+
+fn prompt_remove_users(p: &mut Project) -> Result<(), PiqoError> {
     let users_to_remove =
         MultiSelect::new("Select users to remove", get_users_mod_list(p)).prompt()?;
 
@@ -208,7 +379,7 @@ fn prompt_remove_users(p: &mut Project) -> Result<(), inquire::error::InquireErr
     Ok(())
 }
 
-fn prompt_create_user_manually(p: &mut Project) -> Result<(), inquire::error::InquireError> {
+fn prompt_create_user_manually(p: &mut Project) -> Result<(), PiqoError> {
     let name = inquire::Text::new("Name:").prompt()?;
     let email = inquire::Text::new("Email:").prompt()?;
 
@@ -216,9 +387,7 @@ fn prompt_create_user_manually(p: &mut Project) -> Result<(), inquire::error::In
     Ok(())
 }
 
-pub(crate) fn prompt_create_users_manually(
-    p: &mut Project,
-) -> Result<(), inquire::error::InquireError> {
+pub(crate) fn prompt_create_users_manually(p: &mut Project) -> Result<(), PiqoError> {
     prompt_create_user_manually(p)?;
     loop {
         let create_more = inquire::Select::new("Create more users?", vec!["Yes", "No"]).prompt()?;
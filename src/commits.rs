@@ -0,0 +1,116 @@
+//! Commits referencing tasks in their message, like `piqo#4821` or `fixes piqo#4821`.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::PiqoError;
+
+/// Words that mark a reference as finishing the task, like `fixes piqo#4821`.
+const FIXING_WORDS: [&str; 9] = [
+    "fix", "fixes", "fixed", "close", "closes", "closed", "resolve", "resolves", "resolved",
+];
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub hash: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Commit time, when it was last committed rather than authored.
+    pub at_utc: i64,
+    pub subject: String,
+    pub refs: Vec<TaskRef>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskRef {
+    pub task_id: u64,
+    /// Whether the message says the commit finishes the task.
+    pub fixes: bool,
+}
+
+impl Commit {
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+
+    pub fn references(&self, task_id: u64) -> bool {
+        self.refs.iter().any(|r| r.task_id == task_id)
+    }
+
+    pub fn fixes(&self, task_id: u64) -> bool {
+        self.refs.iter().any(|r| r.task_id == task_id && r.fixes)
+    }
+}
+
+/// Commits reachable from `HEAD` of the repository at `dir` that reference a task, oldest
+/// first.
+///
+/// A repository without commits has none.
+pub fn referencing_commits(dir: &Path) -> Result<Vec<Commit>, PiqoError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "log",
+            "--reverse",
+            "--regexp-ignore-case",
+            "--fixed-strings",
+            "--grep=piqo#",
+            "--format=%H%x1f%an%x1f%ae%x1f%ct%x1f%s%x1f%b%x1e",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Ok(vec![]);
+    }
+
+    let log = String::from_utf8_lossy(&output.stdout);
+    let commits = log
+        .split('\x1e')
+        .filter_map(|record| {
+            let fields = record.trim_start().split('\x1f').collect::<Vec<&str>>();
+            let [hash, author_name, author_email, at_utc, subject, body] = fields[..] else {
+                return None;
+            };
+            let refs = parse_refs(&format!("{}\n{}", subject, body));
+            Some(Commit {
+                hash: hash.to_string(),
+                author_name: author_name.to_string(),
+                author_email: author_email.to_string(),
+                at_utc: at_utc.parse().ok()?,
+                subject: subject.to_string(),
+                refs,
+            })
+        })
+        .filter(|c| !c.refs.is_empty())
+        .collect();
+    Ok(commits)
+}
+
+/// Finds the `piqo#<id>` references in a commit message.
+///
+/// A reference right after a word like `fixes` or `closes` marks the task as finished,
+/// a task referenced more than once fixes it if any of the references does.
+pub fn parse_refs(message: &str) -> Vec<TaskRef> {
+    let lower = message.to_lowercase();
+    let mut refs: Vec<TaskRef> = Vec::new();
+    for (start, _) in lower.match_indices("piqo#") {
+        let digits = lower[start + 5..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>();
+        let Ok(task_id) = digits.parse::<u64>() else {
+            continue;
+        };
+        let previous_word = lower[..start]
+            .trim_end_matches(|c: char| c.is_whitespace() || c == ':')
+            .rsplit(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or("");
+        let fixes = FIXING_WORDS.contains(&previous_word);
+        match refs.iter_mut().find(|r| r.task_id == task_id) {
+            Some(found) => found.fixes |= fixes,
+            None => refs.push(TaskRef { task_id, fixes }),
+        }
+    }
+    refs
+}
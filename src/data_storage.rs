@@ -1,13 +1,18 @@
 use models::Project;
+use serde_json::Value;
 use std::{
-    fs::File,
+    fmt::{self, Display, Formatter},
+    fs::{self, File, TryLockError},
     io::{BufReader, Write},
     path::{Path, PathBuf},
+    process::Command,
 };
 
-use owo_colors::OwoColorize;
-
+use crate::error::PiqoError;
+use crate::merge;
 use crate::models;
+use crate::schema;
+use crate::utils;
 
 pub enum PiqoPath {
     NotFound(gix_discover::upwards::Error),
@@ -15,8 +20,14 @@ pub enum PiqoPath {
     Found(PathBuf),
 }
 
-pub(crate) fn check_piqo_dir() -> PiqoPath {
-    let git_location = gix_discover::upwards(Path::new("."));
+/// Looks for the `.piqo` file or directory in the work tree of the current git repository.
+pub fn check_piqo_dir() -> PiqoPath {
+    discover_piqo_path(Path::new("."))
+}
+
+/// Looks for the `.piqo` file or directory in the work tree of the git repository containing `dir`.
+pub fn discover_piqo_path(dir: &Path) -> PiqoPath {
+    let git_location = gix_discover::upwards(dir);
 
     let git_location = match git_location {
         Ok(git_path) => git_path.0,
@@ -35,52 +46,317 @@ pub(crate) fn check_piqo_dir() -> PiqoPath {
     }
 }
 
-pub(crate) fn store_project(p: &Project) -> Result<(), std::io::Error> {
-    let piqo = check_piqo_dir();
+/// Returns the `.git` directory of the repository containing `dir`.
+pub fn discover_git_dir(dir: &Path) -> Result<PathBuf, PiqoError> {
+    let (git_location, _) =
+        gix_discover::upwards(dir).map_err(|err| PiqoError::NotInGitRepo(Box::new(err)))?;
+    let (git_dir, _) = git_location.into_repository_and_work_tree_directories();
+    Ok(git_dir)
+}
+
+/// Returns the directory git runs hooks from for the repository containing `dir`.
+///
+/// Unlike `hooks` in the `.git` directory, this honors `core.hooksPath` and is the common
+/// directory of linked worktrees.
+pub fn hooks_dir(dir: &Path) -> Result<PathBuf, PiqoError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()?;
+    if !output.status.success() {
+        return Err(PiqoError::InvalidInput(format!(
+            "git could not find the hooks directory: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(dir.join(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Advisory lock on a project, released when dropped.
+pub struct ProjectLock {
+    _file: File,
+}
+
+/// Takes the lock of the project in the current git repository.
+///
+/// Hold it across loading, altering and storing the project so concurrent piqo processes
+/// can't overwrite each other's changes. The lock file lives in the `.git` directory.
+pub fn lock_project() -> Result<ProjectLock, PiqoError> {
+    let git_dir = discover_git_dir(Path::new("."))?;
+    lock_project_at(&git_dir.join("piqo.lock"))
+}
+
+/// Takes an advisory lock on `lock_path`, fails right away if another process holds it.
+pub fn lock_project_at(lock_path: &Path) -> Result<ProjectLock, PiqoError> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(ProjectLock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(PiqoError::ProjectLocked(lock_path.to_path_buf())),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+/// Stores the project in the current git repository.
+pub fn store_project(p: &Project) -> Result<(), PiqoError> {
+    match check_piqo_dir() {
+        PiqoPath::NotFound(err) => Err(PiqoError::NotInGitRepo(Box::new(err))),
+        PiqoPath::FoundNotInit(path) | PiqoPath::Found(path) => store_project_to(p, &path),
+    }
+}
+
+/// Loads the project of the current git repository.
+///
+/// Changes made to it are recorded in its history under the local git email.
+pub fn load_project() -> Result<Project, PiqoError> {
+    let mut p = match check_piqo_dir() {
+        PiqoPath::NotFound(err) => Err(PiqoError::NotInGitRepo(Box::new(err))),
+        PiqoPath::FoundNotInit(path) => Err(PiqoError::NotInitialized(path)),
+        PiqoPath::Found(path) => load_project_from(&path),
+    }?;
+    p.set_actor(utils::get_local_git_email());
+    Ok(p)
+}
+
+/// How a project is laid out on disk.
+///
+/// `File` keeps everything in a single `.piqo` file. `Dir` turns `.piqo` into a directory
+/// holding `project.json` plus one `tasks/<id>.json` per task and one `history/<id>.json`
+/// per change, so concurrent edits of different tasks never touch the same file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageLayout {
+    File,
+    Dir,
+}
+
+impl StorageLayout {
+    pub fn of(path: &Path) -> StorageLayout {
+        if path.is_dir() {
+            StorageLayout::Dir
+        } else {
+            StorageLayout::File
+        }
+    }
+}
+
+impl Display for StorageLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageLayout::File => write!(f, "file"),
+            StorageLayout::Dir => write!(f, "dir"),
+        }
+    }
+}
+
+const PROJECT_FILE: &str = "project.json";
+
+/// An array of the project stored as one file per element in the dir layout.
+struct Collection {
+    field: &'static str,
+    dir: &'static str,
+    /// Elements are read back in the order of this field, ties broken by id.
+    sort_by: &'static str,
+}
+
+const COLLECTIONS: [Collection; 3] = [
+    Collection {
+        field: "tasks",
+        dir: "tasks",
+        sort_by: "created_at_utc",
+    },
+    Collection {
+        field: "history",
+        dir: "history",
+        sort_by: "at_utc",
+    },
+    Collection {
+        field: "time_entries",
+        dir: "time",
+        sort_by: "started_at_utc",
+    },
+];
+
+/// Stores the project at `path`, keeping the layout already in use there.
+pub fn store_project_to(p: &Project, path: &Path) -> Result<(), PiqoError> {
+    store_project_as(p, path, StorageLayout::of(path))
+}
+
+pub fn store_project_as(p: &Project, path: &Path, layout: StorageLayout) -> Result<(), PiqoError> {
+    let mut value = serde_json::to_value(p)
+        .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?;
 
-    let path = match piqo {
-        PiqoPath::NotFound(err) => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Unable to store .piqo file: {}", err),
-            ))
+    // Someone else wrote the project since we loaded it, put our changes on top of theirs
+    if let Some(loaded) = &p.loaded_state
+        && path.exists()
+    {
+        let current = serde_json::to_value(load_project_from(path)?)
+            .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?;
+        if &current != loaded {
+            let merged = merge::merge_values_strict(Some(loaded), &value, &current);
+            if !merged.is_clean() {
+                return Err(PiqoError::ChangedOnDisk(merged.conflicts));
+            }
+            value = merged.value;
         }
-        PiqoPath::FoundNotInit(path) | PiqoPath::Found(path) => Some(path),
-    };
-    let mut file = File::create(path.unwrap())?;
-    let serialized = serde_json::to_string_pretty(p)?;
-    file.write_all(serialized.as_bytes())?;
+    }
+
+    match layout {
+        StorageLayout::File => write_json(path, &value),
+        StorageLayout::Dir => write_dir(path, value),
+    }
+}
+
+/// Loads the project at `path`, upgrading it to the current schema version.
+///
+/// The project remembers what it looked like when loaded, storing it later merges in
+/// changes written to `path` in the meantime.
+pub fn load_project_from(path: &Path) -> Result<Project, PiqoError> {
+    let mut value = read_project_value(path)?;
+    schema::migrate(&mut value)?;
+    let mut p: Project = serde_json::from_value(value)
+        .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?;
+    p.loaded_state = Some(
+        serde_json::to_value(&p)
+            .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?,
+    );
+    Ok(p)
+}
+
+/// Reads the project at `path` as stored, without any migrations applied.
+pub fn read_project_value(path: &Path) -> Result<Value, PiqoError> {
+    if !path.exists() {
+        return Err(PiqoError::NotInitialized(path.to_path_buf()));
+    }
+    match StorageLayout::of(path) {
+        StorageLayout::File => read_json(path),
+        StorageLayout::Dir => read_dir(path),
+    }
+}
+
+/// Rewrites the project at `path` in the given layout.
+pub fn migrate_layout(path: &Path, to: StorageLayout) -> Result<(), PiqoError> {
+    if StorageLayout::of(path) == to {
+        return Ok(());
+    }
+    let p = load_project_from(path)?;
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    remove_path(&tmp_path)?;
+
+    store_project_as(&p, &tmp_path, to)?;
+    remove_path(path)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<(), PiqoError> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn read_json(path: &Path) -> Result<Value, PiqoError> {
+    let file = File::open(path)?;
+    let rdr = BufReader::new(file);
+    serde_json::from_reader(rdr)
+        .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<(), PiqoError> {
+    let serialized = serde_json::to_string_pretty(value)
+        .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?;
+    write_atomic(path, serialized.as_bytes())?;
     Ok(())
 }
 
-pub(crate) fn load_project() -> Result<Project, std::io::Error> {
-    let piqo = check_piqo_dir();
+/// Writes to a temporary file next to `path` and renames it into place, so an interrupted
+/// write never leaves a truncated file behind.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn read_dir(path: &Path) -> Result<Value, PiqoError> {
+    let mut project = read_json(&path.join(PROJECT_FILE))?;
 
-    let path = match piqo {
-        PiqoPath::NotFound(err) => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "Unable to load project, .piqo file could not be found: {}",
-                    err
-                ),
-            ));
+    for collection in &COLLECTIONS {
+        let mut elements = Vec::new();
+        let dir = path.join(collection.dir);
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let element_path = entry?.path();
+                if element_path.extension().is_some_and(|e| e == "json") {
+                    elements.push(read_json(&element_path)?);
+                }
+            }
         }
-        PiqoPath::Found(path) => Some(path),
-        PiqoPath::FoundNotInit(path) => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "Unable to load project, .piqo file could not be found: {:?} - {} ",
-                    path,
-                    "try running `piqo init`".green(),
-                ),
-            ));
+        elements.sort_by_key(|e| (e[collection.sort_by].as_i64(), e["id"].as_u64()));
+
+        if let Some(project) = project.as_object_mut() {
+            project.insert(collection.field.to_string(), Value::Array(elements));
         }
-    };
+    }
+    Ok(project)
+}
 
-    let file = File::open(path.unwrap())?;
-    let rdr = BufReader::new(file);
-    let p: Project = serde_json::from_reader(rdr)?;
-    Ok(p)
+fn write_dir(path: &Path, mut project: Value) -> Result<(), PiqoError> {
+    let mut collections = Vec::new();
+    for collection in &COLLECTIONS {
+        let elements = match project
+            .as_object_mut()
+            .and_then(|p| p.remove(collection.field))
+        {
+            Some(Value::Array(elements)) => elements,
+            _ => vec![],
+        };
+        collections.push((path.join(collection.dir), elements));
+    }
+    fs::create_dir_all(path)?;
+    write_json(&path.join(PROJECT_FILE), &project)?;
+
+    for (dir, elements) in collections {
+        fs::create_dir_all(&dir)?;
+        let mut element_files = Vec::new();
+        for element in &elements {
+            let element_path = dir.join(format!("{}.json", element["id"]));
+            // Leave untouched elements alone, keeps mtimes and the work tree quiet
+            let unchanged =
+                element_path.exists() && read_json(&element_path).is_ok_and(|e| &e == element);
+            if !unchanged {
+                write_json(&element_path, element)?;
+            }
+            element_files.push(element_path);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let element_path = entry?.path();
+            if element_path.extension().is_some_and(|e| e == "json")
+                && !element_files.contains(&element_path)
+            {
+                fs::remove_file(element_path)?;
+            }
+        }
+    }
+    Ok(())
 }
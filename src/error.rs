@@ -0,0 +1,163 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use owo_colors::OwoColorize;
+
+use crate::merge::Conflict;
+
+#[derive(Debug)]
+pub enum PiqoError {
+    NotInGitRepo(Box<gix_discover::upwards::Error>),
+    NotInitialized(PathBuf),
+    AlreadyInitialized(PathBuf),
+    CorruptProjectFile(PathBuf, serde_json::Error),
+    /// The project was written by a newer piqo, holds the version found.
+    UnsupportedSchemaVersion(u32),
+    TaskNotFound(u64),
+    UserNotFound(String),
+    CategoryNotFound(String),
+    LabelNotFound(String),
+    /// A value was not given as an argument and we can't prompt for it.
+    MissingInput(String),
+    InvalidInput(String),
+    /// Another piqo process holds the lock file at the path.
+    ProjectLocked(PathBuf),
+    /// The project was changed on disk while we were altering it and the changes conflict.
+    ChangedOnDisk(Vec<Conflict>),
+    /// Linking the tasks would make a task block itself, holds the chain of tasks.
+    DependencyCycle(Vec<u64>),
+    /// Paths of the fields a merge could not resolve.
+    MergeConflicts(Vec<String>),
+    Prompt(inquire::InquireError),
+    Io(std::io::Error),
+}
+
+impl PiqoError {
+    /// Process exit code, lets scripts tell the failures apart.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            PiqoError::Io(_) => 1,
+            PiqoError::MissingInput(_) | PiqoError::InvalidInput(_) => 2,
+            PiqoError::NotInGitRepo(_) => 3,
+            PiqoError::NotInitialized(_) => 4,
+            PiqoError::AlreadyInitialized(_) => 5,
+            PiqoError::CorruptProjectFile(_, _) => 6,
+            PiqoError::TaskNotFound(_) => 7,
+            PiqoError::UserNotFound(_) => 8,
+            PiqoError::CategoryNotFound(_) => 9,
+            PiqoError::Prompt(
+                inquire::InquireError::OperationCanceled
+                | inquire::InquireError::OperationInterrupted,
+            ) => 130,
+            PiqoError::Prompt(_) => 10,
+            PiqoError::MergeConflicts(_) => 11,
+            PiqoError::UnsupportedSchemaVersion(_) => 12,
+            PiqoError::ProjectLocked(_) => 13,
+            PiqoError::ChangedOnDisk(_) => 14,
+            PiqoError::DependencyCycle(_) => 15,
+            PiqoError::LabelNotFound(_) => 16,
+        }
+    }
+}
+
+impl Display for PiqoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PiqoError::NotInGitRepo(err) => {
+                write!(f, "not inside a git repository: {}", err)
+            }
+            PiqoError::NotInitialized(path) => write!(
+                f,
+                "no project found at {} - {}",
+                path.display(),
+                "try running `piqo init`".green()
+            ),
+            PiqoError::AlreadyInitialized(path) => {
+                write!(f, "project already initialized at {}", path.display())
+            }
+            PiqoError::CorruptProjectFile(path, err) => {
+                write!(f, "unable to read project file {}: {}", path.display(), err)
+            }
+            PiqoError::UnsupportedSchemaVersion(version) => write!(
+                f,
+                "project has schema version {} but this piqo only supports up to {} - {}",
+                version,
+                crate::schema::SCHEMA_VERSION,
+                "upgrade piqo to use it".green()
+            ),
+            PiqoError::TaskNotFound(id) => write!(f, "task with id {} not found", id),
+            PiqoError::UserNotFound(user) => write!(f, "user {} not found", user),
+            PiqoError::CategoryNotFound(category) => write!(f, "category {} not found", category),
+            PiqoError::LabelNotFound(label) => write!(f, "label {} not found", label),
+            PiqoError::MissingInput(missing) => write!(
+                f,
+                "missing {}, pass it as an argument or run piqo in a terminal",
+                missing
+            ),
+            PiqoError::InvalidInput(msg) => write!(f, "{}", msg),
+            PiqoError::ProjectLocked(path) => write!(
+                f,
+                "project is locked by another piqo process ({}), try again when it is done",
+                path.display()
+            ),
+            PiqoError::ChangedOnDisk(conflicts) => {
+                write!(
+                    f,
+                    "project changed on disk while piqo was running, \
+                     nothing was stored as it would overwrite these changes:"
+                )?;
+                for conflict in conflicts {
+                    write!(
+                        f,
+                        "\n  {}\n    {} {}\n    {} {}",
+                        conflict.path,
+                        "- on disk:".red(),
+                        conflict.theirs,
+                        "+ yours:  ".green(),
+                        conflict.ours
+                    )?;
+                }
+                Ok(())
+            }
+            PiqoError::DependencyCycle(chain) => write!(
+                f,
+                "task {} can't block task {}, it would create a cycle: {}",
+                chain[0],
+                chain[1],
+                chain
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" -> ")
+            ),
+            PiqoError::MergeConflicts(paths) => write!(
+                f,
+                "{} conflicting fields: {}",
+                paths.len(),
+                paths.join(", ")
+            ),
+            PiqoError::Prompt(err) => write!(f, "{}", err),
+            PiqoError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PiqoError {}
+
+impl From<inquire::InquireError> for PiqoError {
+    fn from(err: inquire::InquireError) -> Self {
+        match err {
+            inquire::InquireError::NotTTY => {
+                PiqoError::MissingInput("input, the input device is not a TTY".to_string())
+            }
+            inquire::InquireError::IO(err) => PiqoError::Io(err),
+            err => PiqoError::Prompt(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for PiqoError {
+    fn from(err: std::io::Error) -> Self {
+        PiqoError::Io(err)
+    }
+}
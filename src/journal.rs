@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::data_storage;
+use crate::error::PiqoError;
+use crate::merge;
+use crate::models::{Operation, Project};
+
+/// Older entries are dropped once the journal holds this many.
+pub const MAX_ENTRIES: usize = 100;
+
+/// Local record of the changes made by piqo commands, used to undo and redo them.
+///
+/// It lives in the `.git` directory so it is never committed, every clone has its own.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+    pub undo: Vec<JournalEntry>,
+    pub redo: Vec<JournalEntry>,
+}
+
+/// What a command changed in the project, leaving out its history.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub at_utc: i64,
+    pub command: String,
+    pub changes: Vec<Change>,
+}
+
+/// A value a command added, removed or replaced, `None` on the side it didn't exist.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub path: Vec<Segment>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    /// Where an added or removed array element was, to put it back there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+
+/// A step into the project document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Segment {
+    Field(String),
+    /// The element of an array of objects whose `key` field holds `value`.
+    Element {
+        key: String,
+        value: Value,
+    },
+}
+
+/// Returns where the journal of the repository containing `dir` is kept.
+pub fn journal_path(dir: &Path) -> Result<PathBuf, PiqoError> {
+    Ok(data_storage::discover_git_dir(dir)?
+        .join("piqo")
+        .join("journal.json"))
+}
+
+impl Journal {
+    pub fn load(path: &Path) -> Result<Journal, PiqoError> {
+        if !path.exists() {
+            return Ok(Journal::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), PiqoError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let serialized = serde_json::to_string(self)
+            .map_err(|err| PiqoError::CorruptProjectFile(path.to_path_buf(), err))?;
+        data_storage::write_atomic(path, serialized.as_bytes())?;
+        Ok(())
+    }
+
+    /// Records what `command` changed in the project since it was loaded.
+    ///
+    /// Anything left to redo is forgotten, like in an editor.
+    pub fn record(&mut self, p: &Project, command: &str) {
+        let Some(before) = &p.loaded_state else {
+            return;
+        };
+        let Ok(after) = serde_json::to_value(p) else {
+            return;
+        };
+        let mut changes = Vec::new();
+        diff(
+            &without_history(before),
+            &without_history(&after),
+            &mut Vec::new(),
+            &mut changes,
+        );
+        if changes.is_empty() {
+            return;
+        }
+        self.undo.push(JournalEntry {
+            at_utc: chrono::Utc::now().timestamp(),
+            command: command.to_string(),
+            changes,
+        });
+        if self.undo.len() > MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Reverts the last `count` recorded commands, keeping anything changed after them.
+    ///
+    /// Fails when a later change conflicts with reverting, neither the project nor the
+    /// journal should be stored then.
+    pub fn undo(&mut self, p: &mut Project, count: usize) -> Result<(), PiqoError> {
+        for _ in 0..count {
+            let Some(entry) = self.undo.pop() else {
+                break;
+            };
+            apply(p, &entry.changes, false)?;
+            p.record(
+                Operation::ChangesUndone,
+                0,
+                None,
+                Some(entry.command.clone().into()),
+            );
+            self.redo.push(entry);
+        }
+        Ok(())
+    }
+
+    /// Replays the last `count` undone commands.
+    pub fn redo(&mut self, p: &mut Project, count: usize) -> Result<(), PiqoError> {
+        for _ in 0..count {
+            let Some(entry) = self.redo.pop() else {
+                break;
+            };
+            apply(p, &entry.changes, true)?;
+            p.record(
+                Operation::ChangesRedone,
+                0,
+                None,
+                Some(entry.command.clone().into()),
+            );
+            self.undo.push(entry);
+        }
+        Ok(())
+    }
+}
+
+/// Applies the changes to the project, or reverts them, keeping anything changed since.
+///
+/// Fails when a value the changes touch isn't what they left it as anymore. Timestamps of
+/// when objects were last updated keep the newer time instead.
+fn apply(p: &mut Project, changes: &[Change], forward: bool) -> Result<(), PiqoError> {
+    let mut value = serde_json::to_value(&*p).map_err(|err| {
+        PiqoError::InvalidInput(format!("unable to serialize the project: {}", err))
+    })?;
+
+    let mut conflicts = Vec::new();
+    let ordered: Box<dyn Iterator<Item = &Change>> = match forward {
+        true => Box::new(changes.iter()),
+        false => Box::new(changes.iter().rev()),
+    };
+    for change in ordered {
+        let (expected, target) = match forward {
+            true => (&change.before, &change.after),
+            false => (&change.after, &change.before),
+        };
+        if !apply_change(&mut value, change, expected.as_ref(), target.as_ref()) {
+            conflicts.push(path_to_string(&change.path));
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(PiqoError::MergeConflicts(conflicts));
+    }
+
+    let mut reverted: Project = serde_json::from_value(value)
+        .map_err(|err| PiqoError::InvalidInput(format!("unable to apply the change: {}", err)))?;
+    reverted.actor = p.actor.take();
+    reverted.loaded_state = p.loaded_state.take();
+    *p = reverted;
+    Ok(())
+}
+
+/// Replaces the value at the change's path with `target` if it still is `expected`,
+/// returning whether it was.
+fn apply_change(
+    root: &mut Value,
+    change: &Change,
+    expected: Option<&Value>,
+    target: Option<&Value>,
+) -> bool {
+    let Some((last, parents)) = change.path.split_last() else {
+        return false;
+    };
+    let Some(parent) = parents.iter().try_fold(root, step_into) else {
+        return false;
+    };
+
+    match (last, parent) {
+        (Segment::Field(field), Value::Object(object)) => {
+            let current = object.get(field);
+            if let (Some(Value::Number(current)), Some(Value::Number(target))) = (current, target)
+                && field == merge::TIEBREAK_FIELD
+            {
+                let newest = current.as_i64().max(target.as_i64());
+                object.insert(field.clone(), newest.into());
+                return true;
+            }
+            // Stored changes can't tell a null field from a missing one
+            if current.filter(|v| !v.is_null()) != expected.filter(|v| !v.is_null()) {
+                return false;
+            }
+            match target {
+                Some(target) => object.insert(field.clone(), target.clone()),
+                None => object.remove(field),
+            };
+            true
+        }
+        (Segment::Element { key, value }, Value::Array(elements)) => {
+            let found = elements.iter().position(|e| e.get(key) == Some(value));
+            if found.map(|i| &elements[i]) != expected {
+                return false;
+            }
+            match (found, target) {
+                (Some(i), Some(target)) => elements[i] = target.clone(),
+                (Some(i), None) => {
+                    elements.remove(i);
+                }
+                (None, Some(target)) => {
+                    let at = change
+                        .position
+                        .unwrap_or(elements.len())
+                        .min(elements.len());
+                    elements.insert(at, target.clone());
+                }
+                (None, None) => {}
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn step_into<'a>(value: &'a mut Value, segment: &Segment) -> Option<&'a mut Value> {
+    match segment {
+        Segment::Field(field) => value.get_mut(field),
+        Segment::Element { key, value: id } => value
+            .as_array_mut()?
+            .iter_mut()
+            .find(|e| e.get(key) == Some(id)),
+    }
+}
+
+/// Collects the changes turning `before` into `after`.
+///
+/// Objects are compared field by field and arrays of objects element by element, matched
+/// up like when merging. Anything else, and arrays whose elements were reordered, change
+/// as a whole.
+fn diff(before: &Value, after: &Value, path: &mut Vec<Segment>, changes: &mut Vec<Change>) {
+    if before == after {
+        return;
+    }
+    let whole = |path: &Vec<Segment>| Change {
+        path: path.clone(),
+        before: Some(before.clone()),
+        after: Some(after.clone()),
+        position: None,
+    };
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            for field in b.keys().chain(a.keys().filter(|k| !b.contains_key(*k))) {
+                path.push(Segment::Field(field.clone()));
+                match (b.get(field), a.get(field)) {
+                    (Some(b), Some(a)) => diff(b, a, path, changes),
+                    (b, a) => changes.push(Change {
+                        path: path.clone(),
+                        before: b.cloned(),
+                        after: a.cloned(),
+                        position: None,
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (Value::Array(b), Value::Array(a)) => {
+            let Some(key) = merge::array_key(None, b, a) else {
+                changes.push(whole(path));
+                return;
+            };
+            let find = |elements: &[Value], id: &Value| {
+                elements.iter().position(|e| e.get(key) == Some(id))
+            };
+            let kept_order = |x: &[Value], y: &[Value]| {
+                x.iter()
+                    .filter(|e| find(y, &e[key]).is_some())
+                    .map(|e| e[key].clone())
+                    .collect::<Vec<Value>>()
+            };
+            if kept_order(b, a) != kept_order(a, b) {
+                changes.push(whole(path));
+                return;
+            }
+            for (position, element) in b.iter().enumerate() {
+                path.push(element_segment(key, element));
+                match find(a, &element[key]) {
+                    Some(i) => diff(element, &a[i], path, changes),
+                    None => changes.push(Change {
+                        path: path.clone(),
+                        before: Some(element.clone()),
+                        after: None,
+                        position: Some(position),
+                    }),
+                }
+                path.pop();
+            }
+            for (position, element) in a.iter().enumerate() {
+                if find(b, &element[key]).is_none() {
+                    path.push(element_segment(key, element));
+                    changes.push(Change {
+                        path: path.clone(),
+                        before: None,
+                        after: Some(element.clone()),
+                        position: Some(position),
+                    });
+                    path.pop();
+                }
+            }
+        }
+        _ => changes.push(whole(path)),
+    }
+}
+
+fn element_segment(key: &str, element: &Value) -> Segment {
+    Segment::Element {
+        key: key.to_string(),
+        value: element[key].clone(),
+    }
+}
+
+/// Writes the path like merge conflicts do, e.g. `tasks[id=4821].name`.
+fn path_to_string(path: &[Segment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Field(field) if out.is_empty() => out.push_str(field),
+            Segment::Field(field) => out.push_str(&format!(".{}", field)),
+            Segment::Element { key, value } => out.push_str(&format!("[{}={}]", key, value)),
+        }
+    }
+    out
+}
+
+fn without_history(project: &Value) -> Value {
+    let mut project = project.clone();
+    if let Some(project) = project.as_object_mut() {
+        project.remove("history");
+    }
+    project
+}
@@ -0,0 +1,43 @@
+//! Piqo keeps the tasks of a project in a `.piqo` file inside its git repository.
+//!
+//! The `piqo` binary is a thin cli on top of this library, other tools can use it to
+//! read and alter a project without shelling out.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! let mut project = piqo::load_project_from(Path::new("repo/.piqo"))?;
+//! let task_id = project.add_task("Write docs".to_string());
+//! project.assign_task(project.users[0].id, task_id)?;
+//! piqo::store_project_to(&project, Path::new("repo/.piqo"))?;
+//! # Ok::<(), piqo::PiqoError>(())
+//! ```
+
+pub mod authors;
+pub mod branches;
+pub mod commits;
+pub mod data_storage;
+pub mod error;
+pub mod journal;
+pub mod merge;
+pub mod models;
+mod printing;
+mod project;
+pub mod query;
+pub mod schema;
+pub mod tasks_csv;
+pub mod utils;
+
+pub use authors::Author;
+pub use commits::{Commit, TaskRef};
+pub use data_storage::{
+    PiqoPath, ProjectLock, StorageLayout, discover_piqo_path, load_project, load_project_from,
+    lock_project, lock_project_at, store_project, store_project_to,
+};
+pub use error::PiqoError;
+pub use journal::{Change, Journal, JournalEntry, Segment};
+pub use models::{
+    Category, CheckListItem, Estimate, HistoryEvent, Label, LabelColor, Operation, Priority,
+    Project, Recurrence, RecurrenceUnit, Task, TaskJson, TimeEntry, TimeGrouping, User,
+};
+pub use query::{ArchivedTasks, Query};
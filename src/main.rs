@@ -1,14 +1,16 @@
+use std::process::ExitCode;
+
+use owo_colors::OwoColorize;
+
 mod commands;
-mod data_storage;
-mod models;
-mod printing;
-mod utils;
-mod project;
 
-fn main() {
+fn main() -> ExitCode {
     // env::set_var("RUST_BACKTRACE", "full");
-    let result = commands::parse();
-    if let Err(e) = result {
-        println!("Error: {}", e);
+    match commands::parse() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red(), e);
+            ExitCode::from(e.exit_code())
+        }
     }
 }
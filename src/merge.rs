@@ -0,0 +1,523 @@
+use rand::Rng;
+use serde_json::{Map, Value};
+
+/// Fields used to match up the elements of an array of objects between versions.
+const KEY_FIELDS: [&str; 2] = ["id", "index"];
+/// Key of elements that are renumbered when both sides added different ones under the same
+/// key, checklist indexes are only unique within their task and nothing refers to them.
+const RENUMBERED_KEY: &str = "index";
+pub(crate) const TIEBREAK_FIELD: &str = "updated_at_utc";
+const PLACEHOLDER_PREFIX: &str = "<<piqo-conflict-";
+
+/// A field both sides changed to different values where neither side could be preferred.
+#[derive(Debug)]
+pub struct Conflict {
+    pub path: String,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Debug)]
+pub struct Merged {
+    pub value: Value,
+    pub conflicts: Vec<Conflict>,
+    /// Prefix of the strings standing in for conflicts in `value`.
+    placeholder: String,
+}
+
+struct Context {
+    conflicts: Vec<Conflict>,
+    use_tiebreak: bool,
+    placeholder: String,
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Ours,
+    Theirs,
+}
+
+impl Merged {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// Pretty prints the merged document, conflicting fields are written with git style
+    /// conflict markers holding both versions.
+    pub fn to_string_with_markers(&self) -> String {
+        let pretty = serde_json::to_string_pretty(&self.value).unwrap();
+        if self.is_clean() {
+            return pretty;
+        }
+
+        let mut out = Vec::new();
+        for line in pretty.lines() {
+            let Some((prefix, conflict, suffix)) = self.placeholder_in(line) else {
+                out.push(line.to_string());
+                continue;
+            };
+            let indent = &line[..line.len() - line.trim_start().len()];
+
+            out.push("<<<<<<< ours".to_string());
+            out.push(render_side(prefix, &conflict.ours, suffix, indent));
+            out.push("=======".to_string());
+            out.push(render_side(prefix, &conflict.theirs, suffix, indent));
+            out.push(">>>>>>> theirs".to_string());
+        }
+        out.join("\n")
+    }
+
+    /// Splits a line holding a conflict placeholder into the text before it, the conflict
+    /// and the text after it.
+    fn placeholder_in<'a>(&self, line: &'a str) -> Option<(&'a str, &Conflict, &'a str)> {
+        let start = line.find(&format!("\"{}", self.placeholder))?;
+        let rest = &line[start + 1 + self.placeholder.len()..];
+        let end = rest.find(">>\"")?;
+        let conflict = self.conflicts.get(rest[..end].parse::<usize>().ok()?)?;
+        Some((&line[..start], conflict, &rest[end + 3..]))
+    }
+}
+
+fn render_side(prefix: &str, value: &Value, suffix: &str, indent: &str) -> String {
+    let value = serde_json::to_string_pretty(value)
+        .unwrap()
+        .replace('\n', &format!("\n{}", indent));
+    format!("{}{}{}", prefix, value, suffix)
+}
+
+/// Three-way merges two versions of a project document against their common ancestor.
+///
+/// Objects are merged field by field and arrays of objects are matched up by their `id`
+/// (or `index` for checklist items), so additions and edits from both sides are kept.
+/// When both sides changed a field of an object carrying `updated_at_utc`, the most
+/// recently updated side wins. Anything else both sides changed is a conflict.
+///
+/// Elements both sides added under the same key but with different names are kept apart,
+/// checklist items by renumbering theirs and anything else as a conflict.
+pub fn merge_values(base: Option<&Value>, ours: &Value, theirs: &Value) -> Merged {
+    merge_with(base, ours, theirs, true)
+}
+
+/// Like `merge_values`, but never lets a newer `updated_at_utc` overrule a change.
+pub fn merge_values_strict(base: Option<&Value>, ours: &Value, theirs: &Value) -> Merged {
+    merge_with(base, ours, theirs, false)
+}
+
+fn merge_with(base: Option<&Value>, ours: &Value, theirs: &Value, use_tiebreak: bool) -> Merged {
+    let mut ctx = Context {
+        conflicts: Vec::new(),
+        use_tiebreak,
+        placeholder: unused_placeholder(&[base, Some(ours), Some(theirs)]),
+    };
+    let value = if base.is_none() && collides(ours, theirs) {
+        // A lone task, as stored per file in the dir layout, added on both sides
+        conflict(ours, theirs, "", &mut ctx)
+    } else {
+        // and otherwise tiebroken as a whole
+        let preferred = tiebreak(ours, theirs).filter(|_| use_tiebreak);
+        merge(base, ours, theirs, preferred, "", &mut ctx)
+    };
+    Merged {
+        value,
+        conflicts: ctx.conflicts,
+        placeholder: ctx.placeholder,
+    }
+}
+
+/// A placeholder prefix found nowhere in the documents, so no text of a task can be
+/// mistaken for a conflict.
+fn unused_placeholder(documents: &[Option<&Value>]) -> String {
+    let texts = documents
+        .iter()
+        .flatten()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>();
+    let mut rng = rand::rng();
+    loop {
+        let placeholder = format!("{}{:016x}-", PLACEHOLDER_PREFIX, rng.random::<u64>());
+        if !texts.iter().any(|t| t.contains(&placeholder)) {
+            return placeholder;
+        }
+    }
+}
+
+fn merge(
+    base: Option<&Value>,
+    ours: &Value,
+    theirs: &Value,
+    preferred: Option<Side>,
+    path: &str,
+    ctx: &mut Context,
+) -> Value {
+    if ours == theirs || base == Some(theirs) {
+        return ours.clone();
+    }
+    if base == Some(ours) {
+        return theirs.clone();
+    }
+
+    match (ours, theirs) {
+        (Value::Object(o), Value::Object(t)) => {
+            let b = base.and_then(|b| b.as_object());
+            Value::Object(merge_objects(b, o, t, preferred, path, ctx))
+        }
+        (Value::Array(o), Value::Array(t)) => {
+            let b = base.and_then(|b| b.as_array());
+            if let Some(key) = array_key(b, o, t) {
+                return Value::Array(merge_keyed(b, o, t, key, preferred, path, ctx));
+            }
+            if all_scalars(b, o, t) {
+                return Value::Array(merge_sets(b, o, t));
+            }
+            pick(ours, theirs, preferred, path, ctx)
+        }
+        _ => pick(ours, theirs, preferred, path, ctx),
+    }
+}
+
+fn pick(
+    ours: &Value,
+    theirs: &Value,
+    preferred: Option<Side>,
+    path: &str,
+    ctx: &mut Context,
+) -> Value {
+    match preferred {
+        Some(Side::Ours) => ours.clone(),
+        Some(Side::Theirs) => theirs.clone(),
+        None => conflict(ours, theirs, path, ctx),
+    }
+}
+
+fn conflict(ours: &Value, theirs: &Value, path: &str, ctx: &mut Context) -> Value {
+    ctx.conflicts.push(Conflict {
+        path: path.to_string(),
+        ours: ours.clone(),
+        theirs: theirs.clone(),
+    });
+    Value::String(format!("{}{}>>", ctx.placeholder, ctx.conflicts.len() - 1))
+}
+
+/// Whether both sides added a different element under the same key, told apart by name.
+fn collides(ours: &Value, theirs: &Value) -> bool {
+    ours.get("name").is_some() && ours.get("name") != theirs.get("name")
+}
+
+fn merge_objects(
+    base: Option<&Map<String, Value>>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    preferred: Option<Side>,
+    path: &str,
+    ctx: &mut Context,
+) -> Map<String, Value> {
+    let mut merged = Map::new();
+    let keys = ours
+        .keys()
+        .chain(theirs.keys().filter(|k| !ours.contains_key(*k)));
+    for key in keys {
+        let b = base.and_then(|b| b.get(key));
+        let field_path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        let value = match (ours.get(key), theirs.get(key)) {
+            // Both touched it, the object was last updated by whoever did it last
+            (Some(o), Some(t)) if key == TIEBREAK_FIELD && o.is_i64() && t.is_i64() => {
+                o.as_i64().max(t.as_i64()).into()
+            }
+            (Some(o), Some(t)) => merge(b, o, t, preferred, &field_path, ctx),
+            // Removed on the other side, keep it only if this side changed it
+            (Some(v), None) | (None, Some(v)) if b == Some(v) => continue,
+            (Some(v), None) | (None, Some(v)) => v.clone(),
+            (None, None) => continue,
+        };
+        merged.insert(key.to_string(), value);
+    }
+    merged
+}
+
+fn merge_keyed(
+    base: Option<&Vec<Value>>,
+    ours: &[Value],
+    theirs: &[Value],
+    key: &str,
+    preferred: Option<Side>,
+    path: &str,
+    ctx: &mut Context,
+) -> Vec<Value> {
+    let find = |items: &[Value], id: &Value| -> Option<Value> {
+        items.iter().find(|i| i.get(key) == Some(id)).cloned()
+    };
+    let base = base.map(|b| b.as_slice()).unwrap_or_default();
+
+    let mut merged = Vec::new();
+    let mut renumbered = Vec::new();
+    for o in ours {
+        let id = &o[key];
+        let b = find(base, id);
+        let item_path = format!("{}[{}={}]", path, key, id);
+        match find(theirs, id) {
+            Some(t) if b.is_none() && collides(o, &t) && key == RENUMBERED_KEY => {
+                merged.push(o.clone());
+                renumbered.push(t);
+            }
+            Some(t) if b.is_none() && collides(o, &t) => {
+                merged.push(conflict(o, &t, &item_path, ctx));
+            }
+            Some(t) => {
+                // Elements without a timestamp, like checklist items, go with their parent
+                let preferred = tiebreak(o, &t).filter(|_| ctx.use_tiebreak).or(preferred);
+                merged.push(merge(b.as_ref(), o, &t, preferred, &item_path, ctx));
+            }
+            // Removed by them, keep it only if we changed it
+            None if b.as_ref() == Some(o) => {}
+            None => merged.push(o.clone()),
+        }
+    }
+    for t in theirs {
+        let id = &t[key];
+        if find(ours, id).is_some() {
+            continue;
+        }
+        match find(base, id) {
+            Some(b) if &b == t => {}
+            _ => merged.push(t.clone()),
+        }
+    }
+    let mut last = ours
+        .iter()
+        .chain(theirs.iter())
+        .chain(base.iter())
+        .filter_map(|v| v[key].as_u64())
+        .max()
+        .unwrap_or_default();
+    for mut t in renumbered {
+        last += 1;
+        t[key] = last.into();
+        merged.push(t);
+    }
+    merged
+}
+
+/// Merges arrays of scalars as sets, keeping additions and removals of both sides.
+fn merge_sets(base: Option<&Vec<Value>>, ours: &[Value], theirs: &[Value]) -> Vec<Value> {
+    let in_base = |v: &Value| base.is_some_and(|b| b.contains(v));
+    let mut merged = Vec::new();
+    for v in ours.iter().chain(theirs.iter()) {
+        if merged.contains(v) {
+            continue;
+        }
+        let keep = if in_base(v) {
+            ours.contains(v) && theirs.contains(v)
+        } else {
+            true
+        };
+        if keep {
+            merged.push(v.clone());
+        }
+    }
+    merged
+}
+
+fn tiebreak(ours: &Value, theirs: &Value) -> Option<Side> {
+    let o = ours.get(TIEBREAK_FIELD)?.as_i64()?;
+    let t = theirs.get(TIEBREAK_FIELD)?.as_i64()?;
+    match o.cmp(&t) {
+        std::cmp::Ordering::Greater => Some(Side::Ours),
+        std::cmp::Ordering::Less => Some(Side::Theirs),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+/// Returns the field identifying the elements if all elements are objects sharing one.
+pub(crate) fn array_key(
+    base: Option<&Vec<Value>>,
+    ours: &[Value],
+    theirs: &[Value],
+) -> Option<&'static str> {
+    let mut all = ours
+        .iter()
+        .chain(theirs.iter())
+        .chain(base.into_iter().flatten());
+    let first = all.next()?;
+    let key = KEY_FIELDS.into_iter().find(|k| first.get(*k).is_some())?;
+    all.all(|v| v.get(key).is_some()).then_some(key)
+}
+
+fn all_scalars(base: Option<&Vec<Value>>, ours: &[Value], theirs: &[Value]) -> bool {
+    ours.iter()
+        .chain(theirs.iter())
+        .chain(base.into_iter().flatten())
+        .all(|v| !v.is_array() && !v.is_object())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ids(value: &Value) -> Vec<u64> {
+        value["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["id"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn keyed_arrays_keep_additions_and_removals_of_both_sides() {
+        let base = json!({"tasks": [{"id": 1}, {"id": 2}]});
+        let ours = json!({"tasks": [{"id": 1}, {"id": 2}, {"id": 3}]});
+        let theirs = json!({"tasks": [{"id": 2}, {"id": 4}]});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(ids(&merged.value), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn keyed_arrays_keep_elements_changed_on_the_side_that_kept_them() {
+        let base = json!({"tasks": [{"id": 1, "name": "a"}]});
+        let ours = json!({"tasks": [{"id": 1, "name": "b"}]});
+        let theirs = json!({"tasks": []});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(merged.value, ours);
+    }
+
+    #[test]
+    fn checklist_items_added_on_both_sides_are_renumbered() {
+        let task = |check_list: Value, updated_at: i64| {
+            json!({"tasks": [{
+                "id": 1,
+                "check_list": check_list,
+                "last_check_list_index": 1,
+                "updated_at_utc": updated_at,
+            }]})
+        };
+        let item = |index: u64, name: &str| json!({"index": index, "name": name, "checked": false});
+        let base = task(json!([]), 10);
+        let ours = task(json!([item(1, "add tests")]), 20);
+        let theirs = task(json!([item(1, "write docs")]), 30);
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(
+            merged.value["tasks"][0]["check_list"],
+            json!([item(1, "add tests"), item(2, "write docs")])
+        );
+    }
+
+    #[test]
+    fn tasks_added_on_both_sides_with_the_same_id_conflict() {
+        let base = json!({"tasks": []});
+        let ours = json!({"tasks": [{"id": 1, "name": "a", "updated_at_utc": 20}]});
+        let theirs = json!({"tasks": [{"id": 1, "name": "b", "updated_at_utc": 30}]});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].path, "tasks[id=1]");
+    }
+
+    #[test]
+    fn newer_updated_at_wins_both_sides_changing_a_task() {
+        let base = json!({"tasks": [{"id": 1, "name": "a", "updated_at_utc": 10}]});
+        let ours = json!({"tasks": [{"id": 1, "name": "b", "updated_at_utc": 20}]});
+        let theirs = json!({"tasks": [{"id": 1, "name": "c", "updated_at_utc": 30}]});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(merged.value, theirs);
+    }
+
+    #[test]
+    fn tiebreak_only_decides_fields_both_sides_changed() {
+        let base = json!({"id": 1, "name": "a", "description": "", "updated_at_utc": 10});
+        let ours = json!({"id": 1, "name": "b", "description": "d", "updated_at_utc": 30});
+        let theirs = json!({"id": 1, "name": "c", "description": "", "updated_at_utc": 20});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(
+            merged.value,
+            json!({"id": 1, "name": "b", "description": "d", "updated_at_utc": 30})
+        );
+    }
+
+    #[test]
+    fn strict_merges_ignore_the_tiebreak() {
+        let base = json!({"id": 1, "name": "a", "updated_at_utc": 10});
+        let ours = json!({"id": 1, "name": "b", "updated_at_utc": 20});
+        let theirs = json!({"id": 1, "name": "c", "updated_at_utc": 30});
+
+        let merged = merge_values_strict(Some(&base), &ours, &theirs);
+
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].path, "name");
+        assert_eq!(merged.value["updated_at_utc"], 30);
+    }
+
+    #[test]
+    fn scalar_arrays_merge_as_sets() {
+        let base = json!({"labels": [1, 2, 3]});
+        let ours = json!({"labels": [1, 2, 3, 4]});
+        let theirs = json!({"labels": [2, 3, 5]});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert!(merged.is_clean());
+        assert_eq!(merged.value, json!({"labels": [2, 3, 4, 5]}));
+    }
+
+    #[test]
+    fn conflicts_are_written_with_markers() {
+        let base = json!({"name": "a", "other": 1});
+        let ours = json!({"name": "b", "other": 1});
+        let theirs = json!({"name": "c", "other": 1});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].path, "name");
+        assert_eq!(
+            merged.to_string_with_markers(),
+            [
+                "{",
+                "<<<<<<< ours",
+                "  \"name\": \"b\",",
+                "=======",
+                "  \"name\": \"c\",",
+                ">>>>>>> theirs",
+                "  \"other\": 1",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn text_looking_like_a_placeholder_is_not_a_conflict() {
+        let text = format!("{}0>>", PLACEHOLDER_PREFIX);
+        let base = json!({"name": "a", "description": text});
+        let ours = json!({"name": "b", "description": text});
+        let theirs = json!({"name": "c", "description": text});
+
+        let merged = merge_values(Some(&base), &ours, &theirs);
+        let written = merged.to_string_with_markers();
+
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(written.matches("<<<<<<< ours").count(), 1);
+        assert!(written.contains(&format!("\"description\": \"{}\"", text)));
+    }
+}
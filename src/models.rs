@@ -1,8 +1,15 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{DateTime, Days, Local, Months};
+
+use crate::error::PiqoError;
+use crate::utils;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct Task {
+pub struct Task {
     pub id: u64,
     pub name: String,
     pub description: String,
@@ -14,10 +21,244 @@ pub(crate) struct Task {
     pub assigned_to: Vec<u64>,
     pub check_list: Vec<CheckListItem>,
     pub last_check_list_index: u64,
+    /// Ids of the tasks that can't be finished before this one.
+    pub blocks: Vec<u64>,
+    /// The task this is a subtask of.
+    pub parent_id: Option<u64>,
+    pub priority: Option<Priority>,
+    /// Ids of the project labels the task is tagged with.
+    pub labels: Vec<u64>,
+    pub estimate: Option<Estimate>,
+    /// How often the task comes back once finished.
+    pub recurrence: Option<Recurrence>,
+}
+
+impl Task {
+    pub fn is_archived(&self) -> bool {
+        self.archived_at_utc.is_some()
+    }
+
+    /// Checked and total checklist items, `None` without a checklist.
+    pub fn checklist_progress(&self) -> Option<(usize, usize)> {
+        if self.check_list.is_empty() {
+            return None;
+        }
+        let checked = self.check_list.iter().filter(|c| c.checked).count();
+        Some((checked, self.check_list.len()))
+    }
+}
+
+/// How urgent a task is, `P0` being the most urgent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    P0,
+    P1,
+    P2,
+    P3,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 4] = [Priority::P0, Priority::P1, Priority::P2, Priority::P3];
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::P0 => "P0",
+            Priority::P1 => "P1",
+            Priority::P2 => "P2",
+            Priority::P3 => "P3",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = PiqoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Priority::ALL
+            .into_iter()
+            .find(|p| p.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                PiqoError::InvalidInput(format!(
+                    "invalid priority {}, expected one of P0, P1, P2, P3",
+                    s
+                ))
+            })
+    }
+}
+
+/// Expected effort for a task, either as time or as story points.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Estimate {
+    Minutes(u64),
+    Points(u64),
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Estimate::Minutes(minutes) => {
+                write!(f, "{}", utils::format_time_spent(*minutes as i64 * 60))
+            }
+            Estimate::Points(1) => write!(f, "1 pt"),
+            Estimate::Points(points) => write!(f, "{} pts", points),
+        }
+    }
+}
+
+impl FromStr for Estimate {
+    type Err = PiqoError;
+
+    /// Parses time like `4h` or `1h30m`, or story points like `3pts`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let points = ["pts", "pt", "p"]
+            .into_iter()
+            .find_map(|unit| s.strip_suffix(unit));
+        if let Some(points) = points {
+            return points.trim().parse().map(Estimate::Points).map_err(|_| {
+                PiqoError::InvalidInput(format!("invalid estimate {}, expected e.g. 3pts", s))
+            });
+        }
+        let seconds = utils::parse_time_spent(s).map_err(|_| {
+            PiqoError::InvalidInput(format!(
+                "invalid estimate {}, expected e.g. 4h, 1h30m or 3pts",
+                s
+            ))
+        })?;
+        Ok(Estimate::Minutes(seconds as u64 / 60))
+    }
+}
+
+/// A rule like "every 2 weeks" for tasks that come back once finished.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recurrence {
+    pub every: u32,
+    pub unit: RecurrenceUnit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecurrenceUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Recurrence {
+    /// The first occurrence after `now`, stepping from `from` so a schedule keeps its
+    /// weekday or day of the month.
+    pub fn next_after(&self, from: i64, now: i64) -> i64 {
+        let every = self.every.max(1);
+        let mut next = DateTime::from_timestamp(from, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        loop {
+            let stepped = match self.unit {
+                RecurrenceUnit::Day => next.checked_add_days(Days::new(every.into())),
+                RecurrenceUnit::Week => next.checked_add_days(Days::new(7 * u64::from(every))),
+                RecurrenceUnit::Month => next.checked_add_months(Months::new(every)),
+                RecurrenceUnit::Year => next.checked_add_months(Months::new(12 * every)),
+            };
+            // Only fails way past any sensible date, stop stepping then
+            let Some(stepped) = stepped else {
+                return next.timestamp();
+            };
+            next = stepped;
+            if next.timestamp() > now {
+                return next.timestamp();
+            }
+        }
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            RecurrenceUnit::Day => "day",
+            RecurrenceUnit::Week => "week",
+            RecurrenceUnit::Month => "month",
+            RecurrenceUnit::Year => "year",
+        };
+        match (self.every, self.unit) {
+            (1, RecurrenceUnit::Day) => write!(f, "daily"),
+            (1, _) => write!(f, "{}ly", unit),
+            (every, _) => write!(f, "every {} {}s", every, unit),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = PiqoError;
+
+    /// Parses `daily`, `weekly`, `monthly`, `yearly` or `every <n> <days|weeks|months|years>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            PiqoError::InvalidInput(format!(
+                "invalid recurrence {}, expected daily, weekly, monthly, yearly or e.g. every 2 weeks",
+                s
+            ))
+        };
+        let rule = s.trim().to_lowercase();
+        let (every, unit) = match rule.as_str() {
+            "daily" => (1, "day"),
+            "weekly" => (1, "week"),
+            "monthly" => (1, "month"),
+            "yearly" => (1, "year"),
+            _ => {
+                let words = rule.split_whitespace().collect::<Vec<&str>>();
+                match words.as_slice() {
+                    ["every", unit] => (1, *unit),
+                    ["every", every, unit] => (every.parse().map_err(|_| error())?, *unit),
+                    _ => return Err(error()),
+                }
+            }
+        };
+        let unit = match unit.trim_end_matches('s') {
+            "day" => RecurrenceUnit::Day,
+            "week" => RecurrenceUnit::Week,
+            "month" => RecurrenceUnit::Month,
+            "year" => RecurrenceUnit::Year,
+            _ => return Err(error()),
+        };
+        if every == 0 {
+            return Err(error());
+        }
+        Ok(Recurrence { every, unit })
+    }
+}
+
+/// What `Project::time_report` adds up the logged time by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeGrouping {
+    User,
+    Task,
 }
 
+/// Time a user spent on a task, still running while `ended_at_utc` is missing.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct CheckListItem {
+pub struct TimeEntry {
+    pub id: u64,
+    pub task_id: u64,
+    /// Git email of whoever logged the time.
+    pub actor: Option<String>,
+    pub started_at_utc: i64,
+    pub ended_at_utc: Option<i64>,
+}
+
+impl TimeEntry {
+    /// Seconds logged, counting a running entry up to `now`.
+    pub fn seconds(&self, now: i64) -> i64 {
+        self.ended_at_utc.unwrap_or(now) - self.started_at_utc
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CheckListItem {
     pub index: u64,
     pub name: String,
     pub checked: bool,
@@ -29,9 +270,8 @@ impl Display for CheckListItem {
     }
 }
 
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct TaskJson {
+pub struct TaskJson {
     pub id: u64,
     pub name: String,
     pub description: String,
@@ -48,25 +288,126 @@ pub(crate) struct TaskJson {
     pub assigned_to_ids: Vec<u64>,
     pub assigned_to: Vec<User>,
     pub check_list: Vec<CheckListItem>,
+    /// Checked out of all checklist items like `3/5`, if it has a checklist.
+    pub check_list_progress: Option<String>,
+    pub blocks: Vec<u64>,
+    pub blocked_by: Vec<u64>,
+    pub blocked: bool,
+    pub parent_id: Option<u64>,
+    pub priority: Option<Priority>,
+    pub label_ids: Vec<u64>,
+    pub labels: Vec<Label>,
+    pub estimate: Option<Estimate>,
+    pub recurrence: Option<Recurrence>,
+    /// Seconds logged on the task by everyone.
+    pub time_spent_seconds: i64,
+    /// Percentage of finished subtasks, counting nested subtasks, if it has any.
+    pub subtask_progress: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub(crate) struct Project {
+pub struct Project {
+    pub schema_version: u32,
     pub name: String,
     pub default_category: u64,
+    /// Category `piqo sync-commits` moves fixed tasks to, the first done category if unset.
+    pub commit_fixes_category: Option<u64>,
     pub categories: Vec<Category>,
+    /// Tags for classifying tasks across categories.
+    pub labels: Vec<Label>,
     pub tasks: Vec<Task>,
     pub users: Vec<User>,
+    /// Time logged on tasks, oldest first.
+    pub time_entries: Vec<TimeEntry>,
+    /// Every change made to the project, oldest first.
+    pub history: Vec<HistoryEvent>,
+    /// Git email of whoever is changing the project, recorded in its history.
+    #[serde(skip)]
+    pub(crate) actor: Option<String>,
+    /// The project as it was loaded, used to detect changes made by others before storing.
+    #[serde(skip)]
+    pub(crate) loaded_state: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub(crate) struct Category {
+pub struct Category {
     pub id: u64,
     pub name: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct User {
+pub struct Label {
+    pub id: u64,
+    pub name: String,
+    pub color: LabelColor,
+}
+
+impl Display for Label {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Gray,
+}
+
+impl LabelColor {
+    pub const ALL: [LabelColor; 8] = [
+        LabelColor::Red,
+        LabelColor::Green,
+        LabelColor::Yellow,
+        LabelColor::Blue,
+        LabelColor::Magenta,
+        LabelColor::Cyan,
+        LabelColor::White,
+        LabelColor::Gray,
+    ];
+}
+
+impl Display for LabelColor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LabelColor::Red => "red",
+            LabelColor::Green => "green",
+            LabelColor::Yellow => "yellow",
+            LabelColor::Blue => "blue",
+            LabelColor::Magenta => "magenta",
+            LabelColor::Cyan => "cyan",
+            LabelColor::White => "white",
+            LabelColor::Gray => "gray",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for LabelColor {
+    type Err = PiqoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LabelColor::ALL
+            .into_iter()
+            .find(|c| c.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                PiqoError::InvalidInput(format!(
+                    "invalid color {}, expected one of red, green, yellow, blue, magenta, cyan, white, gray",
+                    s
+                ))
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
     pub id: u64,
     pub git_email: Option<String>,
     pub name: String,
@@ -77,3 +418,96 @@ impl Display for User {
         write!(f, "{}", self.name)
     }
 }
+
+/// A single change to the project.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEvent {
+    pub id: u64,
+    pub at_utc: i64,
+    /// Git email of the user who made the change.
+    pub actor: Option<String>,
+    pub operation: Operation,
+    /// Id of the task, category or user that was changed.
+    pub subject_id: u64,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+impl HistoryEvent {
+    /// The id of the task the event changed, if it changed one.
+    pub fn task_id(&self) -> Option<u64> {
+        match self.operation {
+            Operation::CategoryAdded
+            | Operation::CategoryRenamed
+            | Operation::CategoryRemoved
+            | Operation::CommitFixesCategorySet
+            | Operation::LabelAdded
+            | Operation::LabelEdited
+            | Operation::LabelRemoved
+            | Operation::UserAdded
+            | Operation::UserEdited
+            | Operation::UserRemoved
+            | Operation::ChangesUndone
+            | Operation::ChangesRedone => None,
+            _ => Some(self.subject_id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    TaskAdded,
+    TaskRenamed,
+    TaskDescriptionChanged,
+    TaskMoved,
+    TaskArchived,
+    TaskUnarchived,
+    TaskRemoved,
+    TaskAssigned,
+    TaskUnassigned,
+    TaskDueDateSet,
+    TaskDueDateCleared,
+    /// The task now blocks the task in the new value.
+    TaskLinked,
+    TaskUnlinked,
+    /// The task was made a subtask of the task in the new value.
+    TaskParentChanged,
+    TaskPrioritySet,
+    TaskPriorityCleared,
+    /// The task was tagged with the label in the new value.
+    TaskLabeled,
+    TaskUnlabeled,
+    TaskEstimateSet,
+    TaskEstimateCleared,
+    TaskRecurrenceSet,
+    TaskRecurrenceCleared,
+    /// The task was finished and came back as the task in the new value.
+    TaskRecurred,
+    /// Time tracking on the task started, the new value holds the time entry id.
+    TimerStarted,
+    /// Time tracking on the task stopped, the new value holds the seconds tracked.
+    TimerStopped,
+    /// Time was logged by hand, the new value holds the seconds.
+    TimeLogged,
+    ChecklistItemAdded,
+    ChecklistItemRemoved,
+    ChecklistItemChecked,
+    ChecklistItemUnchecked,
+    ChecklistItemRenamed,
+    ChecklistItemMoved,
+    CategoryAdded,
+    CategoryRenamed,
+    CategoryRemoved,
+    /// The new value holds the category fixed tasks move to, none for the default.
+    CommitFixesCategorySet,
+    LabelAdded,
+    LabelEdited,
+    LabelRemoved,
+    UserAdded,
+    UserEdited,
+    UserRemoved,
+    /// A piqo command was undone, the new value holds the command.
+    ChangesUndone,
+    ChangesRedone,
+}
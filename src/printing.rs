@@ -1,16 +1,21 @@
-use chrono::{DateTime, Utc};
-use owo_colors::OwoColorize;
+use chrono::{DateTime, Local, Utc};
+use owo_colors::{OwoColorize, Style};
+use serde_json::Value;
 use std::collections::HashMap;
 
+use crate::commits::Commit;
 use crate::models::Project;
-use crate::models::{Task, TaskJson, User};
+use crate::models::{
+    Estimate, HistoryEvent, LabelColor, Operation, Priority, Recurrence, Task, TaskJson,
+    TimeGrouping, User,
+};
 use crate::utils;
 use crate::utils::truncate as t;
 use crate::utils::truncate_then_center_align as ct;
 
 impl Project {
-    pub(crate) fn print_tasks_detailed(&self) {
-        for task in self.get_unarchived_tasks() {
+    pub fn print_tasks_detailed(&self, tasks: &[&Task]) {
+        for task in tasks {
             println!("{}", task.name.green());
             if !task.description.is_empty() {
                 Self::print_description(&task.description);
@@ -24,23 +29,29 @@ impl Project {
                 println!("Checklist:");
                 for item in &task.check_list {
                     let line = match item.checked {
-                        true => format!("- [x] {}", item),
-                        false => format!("- [ ] {}", item),
+                        true => format!("- [x] {} {}", item.index, item),
+                        false => format!("- [ ] {} {}", item.index, item),
                     };
                     println!("{}", line);
                 }
             }
-            if task.due_date_utc.is_some() {
+            if !task.labels.is_empty() {
+                println!("Labels: {}", self.colored_labels(task));
+            }
+            if let Some(priority) = task.priority {
+                println!("Priority: {}", priority.style(priority_style(priority)));
+            }
+            if let Some(due_date_utc) = task.due_date_utc {
                 println!(
                     "Due date: {}",
-                    DateTime::from_timestamp(task.due_date_utc.unwrap(), 0).unwrap()
+                    DateTime::from_timestamp(due_date_utc, 0).unwrap()
                 );
             }
             println!("Assigned to:");
             if task.assigned_to.is_empty() {
                 println!("- None");
             } else {
-                self.get_assigned_users(task.id).iter().for_each(|u| {
+                self.users_of(task).iter().for_each(|u| {
                     println!(
                         "- {} <{}>",
                         u.name,
@@ -52,10 +63,7 @@ impl Project {
         }
     }
 
-    pub(crate) fn print_tasks(&self) {
-        let mut tasks = self.tasks.clone();
-        tasks.sort_by(|a, b| a.category.cmp(&b.category));
-
+    pub fn print_tasks(&self, tasks: &[&Task]) {
         let mut category_names = HashMap::new();
         for category in &self.categories {
             category_names.insert(category.id, &category.name);
@@ -67,40 +75,39 @@ impl Project {
         }
 
         let has_any_with_due_date = tasks.iter().any(|t| t.due_date_utc.is_some());
-
-        //TODO: This code is a nightmare.
-        // Suggestion: built formatting string with percentages of how much space each field should take up
-        let mut l = [36, 0, 12, 30];
-
-        if !has_any_with_due_date {
-            println!(
-                "{:^a$}|{:^b$}|{:^c$}",
-                "Name".bold(),
-                "Category".bold(),
-                "Assigned To".bold(),
-                a = l[0],
-                b = l[2],
-                c = l[3],
-            );
-        } else {
-            l[0] = 30;
-            l[1] = 6;
-            println!(
-                "{:^a$}|{:^b$}|{:^c$}|{:^d$}",
-                "Name".bold(),
-                "Due".bold(),
-                "Category".bold(),
-                "Assigned To".bold(),
-                a = l[0],
-                b = l[1],
-                c = l[2],
-                d = l[3],
-            );
+        let has_any_with_priority = tasks.iter().any(|t| t.priority.is_some());
+
+        // The name takes up whatever the other columns leave of the 80 characters
+        let mut headers = vec!["Name"];
+        let mut widths = vec![0];
+        if has_any_with_priority {
+            headers.push("Pri");
+            widths.push(5);
+        }
+        if has_any_with_due_date {
+            headers.push("Due");
+            widths.push(6);
         }
+        let has_any_with_labels = tasks.iter().any(|t| !t.labels.is_empty());
+        if has_any_with_labels {
+            headers.push("Labels");
+            widths.push(14);
+        }
+        headers.extend(["Category", "Assigned To"]);
+        // Assignees give up room for the labels
+        widths.extend([12, if has_any_with_labels { 20 } else { 30 }]);
+        widths[0] = 80 - widths.iter().sum::<usize>() - (widths.len() - 1);
 
+        let header = headers
+            .iter()
+            .zip(&widths)
+            .map(|(h, w)| format!("{:^w$}", h.bold(), w = *w))
+            .collect::<Vec<String>>();
+        println!("{}", header.join("|"));
         println!("{}", "-".repeat(80));
 
-        tasks.iter().for_each(|task| {
+        let assigned_width = widths[widths.len() - 1];
+        self.as_tree(tasks).iter().for_each(|(task, depth)| {
             let users = task
                 .assigned_to
                 .iter()
@@ -109,7 +116,7 @@ impl Project {
 
             let assigned_to = match task.assigned_to.is_empty() {
                 false => {
-                    let total_space = l[3] - (users.len() - 1);
+                    let total_space = assigned_width - (users.len() - 1);
                     let space_per_name = total_space / users.len();
                     users
                         .iter()
@@ -121,46 +128,63 @@ impl Project {
                 true => "None".to_string(),
             };
 
-            if has_any_with_due_date {
-                print!("{:<a$}|", &t(&task.name, l[0]), a = l[0]);
-                if task.due_date_utc.is_some() {
-                    let seconds_till = task.due_date_utc.unwrap() - Utc::now().timestamp();
-                    let (formatted_due_date, style) = &utils::display_due_date_time(seconds_till);
-                    // Printing this way was the only way for me to get colors formatted correctly
-                    print!("{:^b$}|", &formatted_due_date.style(*style), b = l[1]);
-                } else {
-                    print!("{:^b$}|", "", b = l[1]);
-                }
+            // Blocked tasks are marked so they aren't picked up by accident
+            let mut name = match self.is_blocked(task) {
+                true => format!("{}⊘ {}", "  ".repeat(*depth), task.name),
+                false => format!("{}{}", "  ".repeat(*depth), task.name),
+            };
+            if let Some((checked, total)) = task.checklist_progress() {
+                name = format!("{} [{}/{}]", name, checked, total);
+            }
+            if let Some(progress) = self.get_subtask_progress(task.id) {
+                name = format!("{} ({}%)", name, progress);
+            }
 
-                println!(
-                    "{:^c$}|{:^d$}",
-                    &self.get_category(task.category).unwrap().name.to_string(),
-                    &t(&assigned_to, l[3]),
-                    c = l[2],
-                    d = l[3],
-                );
-                return;
+            let mut widths = widths.iter().copied();
+            let name_width = widths.next().unwrap();
+            let mut cells = vec![format!("{:<a$}", t(&name, name_width), a = name_width)];
+            if has_any_with_priority {
+                let w = widths.next().unwrap();
+                // Colors are applied after padding so the escape codes don't count as width
+                cells.push(match task.priority {
+                    Some(priority) => format!("{:^w$}", priority)
+                        .style(priority_style(priority))
+                        .to_string(),
+                    None => " ".repeat(w),
+                });
             }
-            println!(
-                "{:<a$}|{:^b$}|{:^c$}",
-                &t(&task.name, l[0]),
-                // &t(&task.description, l[1]),
-                &t(
-                    &self.get_category(task.category).unwrap().name.to_string(),
-                    l[2]
-                ),
-                &t(&assigned_to, l[3]),
-                a = l[0],
-                b = l[2],
-                c = l[3],
-            );
+            if has_any_with_due_date {
+                let w = widths.next().unwrap();
+                cells.push(match task.due_date_utc {
+                    Some(due_date_utc) => {
+                        let seconds_till = due_date_utc - Utc::now().timestamp();
+                        let (formatted_due_date, style) =
+                            utils::display_due_date_time(seconds_till);
+                        format!("{:^w$}", formatted_due_date)
+                            .style(style)
+                            .to_string()
+                    }
+                    None => " ".repeat(w),
+                });
+            }
+            if has_any_with_labels {
+                let w = widths.next().unwrap();
+                cells.push(self.labels_cell(task, w));
+            }
+            let category = category_names.get(&task.category).unwrap();
+            let w = widths.next().unwrap();
+            cells.push(format!("{:^w$}", t(category, w)));
+            let w = widths.next().unwrap();
+            cells.push(format!("{:^w$}", t(&assigned_to, w)));
+            println!("{}", cells.join("|"));
         });
     }
+
     fn print_status_for_category(&self, category_id: u64) {
         let count = self
-            .tasks
+            .get_tasks_in_category(category_id)
             .iter()
-            .filter(|t| t.category == category_id)
+            .filter(|t| !t.is_archived())
             .count();
         println!(
             "{}: {}",
@@ -168,9 +192,11 @@ impl Project {
             count,
         );
     }
-    pub(crate) fn print_status(&self) {
-        let tasks_msg = format!("Tasks:\t{}", self.get_unarchived_tasks().len(), );
+    pub fn print_status(&self) {
+        let tasks_msg = format!("Tasks:\t{}", self.get_unarchived_tasks().len(),);
         println!("{}", tasks_msg.green());
+        let archived_msg = format!("Archived: {}", self.get_archived_tasks().len());
+        println!("{}", archived_msg.dimmed());
         let users_msg = format!("Users:\t{}", self.users.len());
         println!("{}", users_msg.blue());
         let categories_msg = format!("Categories: {}", self.categories.len());
@@ -183,7 +209,7 @@ impl Project {
             self.print_status_for_category(category.id);
         }
     }
-    pub(crate) fn print_single_task(&self, id: u64) {
+    pub fn print_single_task(&self, id: u64) {
         let task = self.tasks.iter().find(|t| t.id == id);
 
         let width = 60;
@@ -205,11 +231,12 @@ impl Project {
         }
 
         if !t.check_list.is_empty() {
-            utils::print_line_left("Checklist:", width);
+            let (checked, total) = t.checklist_progress().unwrap();
+            utils::print_line_left(&format!("Checklist: {}/{}", checked, total), width);
             for item in &t.check_list {
                 let line = match item.checked {
-                    true => format!("- [x] {}", item),
-                    false => format!("- [ ] {}", item),
+                    true => format!("- [x] {} {}", item.index, item),
+                    false => format!("- [ ] {} {}", item.index, item),
                 };
                 utils::print_line_left(&line, width);
             }
@@ -227,11 +254,72 @@ impl Project {
             width,
         );
 
-        if t.due_date_utc.is_some() {
-            let seconds_till = t.due_date_utc.unwrap() - Utc::now().timestamp();
+        if let Some(due_date_utc) = t.due_date_utc {
+            let seconds_till = due_date_utc - Utc::now().timestamp();
             let (formatted_due_date, style) = &utils::display_due_date_time(seconds_till);
+            utils::print_line_left(&format!("Due: {}", formatted_due_date.style(*style)), width);
+        }
+        if let Some(priority) = t.priority {
+            utils::print_line_left(
+                &format!("Priority: {}", priority.style(priority_style(priority))),
+                width,
+            );
+        }
+        if !t.labels.is_empty() {
+            utils::print_line_left(&format!("Labels: {}", self.colored_labels(t)), width);
+        }
+        if let Some(parent) = t.parent_id {
+            let parent = self.dependency_line(parent);
+            utils::print_line_left(&format!("Subtask of: {}", &parent[2..]), width);
+        }
+        let subtasks = self.get_subtasks(t.id);
+        if !subtasks.is_empty() {
+            utils::print_line_left(
+                &format!(
+                    "Subtasks: {}% done",
+                    self.get_subtask_progress(t.id).unwrap_or(0)
+                ),
+                width,
+            );
+            for subtask in subtasks {
+                utils::print_line_left(&self.dependency_line(subtask.id), width);
+            }
+        }
+        if !t.blocks.is_empty() {
+            utils::print_line_left("Blocks:", width);
+            for id in &t.blocks {
+                utils::print_line_left(&self.dependency_line(*id), width);
+            }
+        }
+        let blockers = self.get_blockers(t.id);
+        if !blockers.is_empty() {
+            utils::print_line_left("Blocked by:", width);
+            for blocker in blockers {
+                utils::print_line_left(&self.dependency_line(blocker.id), width);
+            }
+        }
+        if let Some(recurrence) = t.recurrence {
+            utils::print_line_left(&format!("Repeats: {}", recurrence), width);
+        }
+        if let Some(estimate) = t.estimate {
+            utils::print_line_left(&format!("Estimate: {}", estimate), width);
+        }
+        let time_spent = self.get_time_spent(t.id);
+        if time_spent > 0 {
+            let spent = utils::format_time_spent(time_spent);
+            let line = match t.estimate {
+                Some(Estimate::Minutes(minutes)) => format!(
+                    "Time spent: {} ({}% of the estimate)",
+                    spent,
+                    time_spent * 100 / (minutes as i64 * 60).max(1)
+                ),
+                _ => format!("Time spent: {}", spent),
+            };
+            utils::print_line_left(&line, width);
+        }
+        if let Some(archived_at_utc) = t.archived_at_utc {
             utils::print_line_left(
-                &format!("Due: {}", formatted_due_date.style(*style)),
+                &format!("Archived: {}", Self::unix_time_to_string(archived_at_utc)),
                 width,
             );
         }
@@ -239,7 +327,7 @@ impl Project {
             utils::print_line_left("No users assigned to task", width);
         } else {
             utils::print_line_left("Users assigned to task:", width);
-            self.get_assigned_users(id).iter().for_each(|u| {
+            self.users_of(t).iter().for_each(|u| {
                 utils::print_line_left(
                     &format!(
                         "- {} <{}>",
@@ -254,8 +342,48 @@ impl Project {
         utils::print_divider(width)
     }
 
+    /// Orders the tasks so subtasks follow their parent, paired with their depth.
+    fn as_tree<'a>(&self, tasks: &[&'a Task]) -> Vec<(&'a Task, usize)> {
+        let listed = |id: u64| tasks.iter().any(|t| t.id == id);
+        let mut tree = Vec::new();
+        for root in tasks.iter().filter(|t| !t.parent_id.is_some_and(&listed)) {
+            Self::push_subtree(tasks, root, 0, &mut tree);
+        }
+        // Tasks in a parent cycle have no root, still show them
+        for task in tasks {
+            if !tree.iter().any(|(t, _)| t.id == task.id) {
+                tree.push((task, 0));
+            }
+        }
+        tree
+    }
+
+    fn push_subtree<'a>(
+        tasks: &[&'a Task],
+        task: &'a Task,
+        depth: usize,
+        tree: &mut Vec<(&'a Task, usize)>,
+    ) {
+        if tree.iter().any(|(t, _)| t.id == task.id) {
+            return;
+        }
+        tree.push((task, depth));
+        for subtask in tasks.iter().filter(|t| t.parent_id == Some(task.id)) {
+            Self::push_subtree(tasks, subtask, depth + 1, tree);
+        }
+    }
+
+    fn dependency_line(&self, id: u64) -> String {
+        match self.get_task(id) {
+            Some(task) if task.is_archived() || self.is_done_category(task.category) => {
+                format!("- {} {} (done)", task.id, task.name)
+            }
+            Some(task) => format!("- {} {}", task.id, task.name),
+            None => format!("- {} (removed)", id),
+        }
+    }
 
-    pub(crate) fn print_category(&self, category_id: u64) {
+    pub fn print_category(&self, category_id: u64) {
         let mut user_names = HashMap::new();
         for user in &self.users {
             user_names.insert(&user.id, &user.name);
@@ -268,10 +396,10 @@ impl Project {
             .unwrap();
 
         let tasks = self
-            .tasks
-            .iter()
-            .filter(|t| t.category == category.id)
-            .collect::<Vec<_>>();
+            .get_tasks_in_category(category.id)
+            .into_iter()
+            .filter(|t| !t.is_archived())
+            .collect::<Vec<&Task>>();
         println!("{}", category.name);
         println!("{}", "-".repeat(category.name.len() * 2));
         if !tasks.is_empty() {
@@ -312,54 +440,170 @@ impl Project {
     }
 
     fn unix_time_to_string(time: i64) -> String {
-        DateTime::from_timestamp(time, 0)
-            .unwrap()
-            .to_string()
+        DateTime::from_timestamp(time, 0).unwrap().to_string()
     }
 
-    pub(crate) fn print_tasks_json(&self) {
-        let json_tasks = self
-            .tasks
-            .iter()
-            .map(|t| TaskJson {
-                id: t.id,
-                name: t.name.to_owned(),
-                description: t.description.to_owned(),
-                category_id: t.category,
-                category: self.get_category(t.category).unwrap().name.to_owned(),
-                assigned_to: t
-                    .assigned_to
-                    .iter()
-                    .map(|i| self.get_user(*i).unwrap())
-                    .collect::<Vec<User>>(),
-                assigned_to_ids: t.assigned_to.to_owned(),
-                created_at_utc: Self::unix_time_to_string(t.created_at_utc),
-                created_at_utc_unix: t.created_at_utc,
-                updated_at_utc: Self::unix_time_to_string(t.updated_at_utc),
-                updated_at_utc_unix: t.updated_at_utc,
-                archived_at_utc_unix: t.archived_at_utc.unwrap_or(0),
-                archived_at_utc: match t.archived_at_utc {
-                    Some(archived_at_utc) => Self::unix_time_to_string(archived_at_utc),
-                    None => "".to_string(),
-                },
-                due_date_utc: match t.due_date_utc {
-                    Some(due_date_utc) => Self::unix_time_to_string(due_date_utc),
-                    None => "".to_string(),
-                },
-                due_date_utc_unix: t.due_date_utc.unwrap_or(0),
-                check_list: t.check_list.to_owned(),
-            })
-            .collect::<Vec<_>>();
+    pub fn print_tasks_json(&self, tasks: &[&Task]) {
+        let json_tasks = tasks.iter().map(|t| self.task_json(t)).collect::<Vec<_>>();
 
         let json_format = serde_json::to_string_pretty(&json_tasks).unwrap();
         println!("{}", json_format);
     }
 
-    pub(crate) fn print_categories(&self) {
+    /// The task with its ids resolved, as printed by `piqo list --json`.
+    pub fn task_json(&self, t: &Task) -> TaskJson {
+        TaskJson {
+            id: t.id,
+            name: t.name.to_owned(),
+            description: t.description.to_owned(),
+            category_id: t.category,
+            category: self.get_category(t.category).unwrap().name.to_owned(),
+            assigned_to: t
+                .assigned_to
+                .iter()
+                .map(|i| self.get_user(*i).unwrap())
+                .collect::<Vec<User>>(),
+            assigned_to_ids: t.assigned_to.to_owned(),
+            created_at_utc: Self::unix_time_to_string(t.created_at_utc),
+            created_at_utc_unix: t.created_at_utc,
+            updated_at_utc: Self::unix_time_to_string(t.updated_at_utc),
+            updated_at_utc_unix: t.updated_at_utc,
+            archived_at_utc_unix: t.archived_at_utc.unwrap_or(0),
+            archived_at_utc: match t.archived_at_utc {
+                Some(archived_at_utc) => Self::unix_time_to_string(archived_at_utc),
+                None => "".to_string(),
+            },
+            due_date_utc: match t.due_date_utc {
+                Some(due_date_utc) => Self::unix_time_to_string(due_date_utc),
+                None => "".to_string(),
+            },
+            due_date_utc_unix: t.due_date_utc.unwrap_or(0),
+            check_list: t.check_list.to_owned(),
+            check_list_progress: t
+                .checklist_progress()
+                .map(|(checked, total)| format!("{}/{}", checked, total)),
+            blocks: t.blocks.to_owned(),
+            blocked_by: self.get_blockers(t.id).iter().map(|b| b.id).collect(),
+            blocked: self.is_blocked(t),
+            parent_id: t.parent_id,
+            priority: t.priority,
+            estimate: t.estimate,
+            recurrence: t.recurrence,
+            time_spent_seconds: self.get_time_spent(t.id),
+            label_ids: t.labels.to_owned(),
+            labels: self.labels_of(t).into_iter().cloned().collect(),
+            subtask_progress: self.get_subtask_progress(t.id),
+        }
+    }
+
+    pub fn print_categories(&self) {
         self.categories.iter().for_each(|c| println!("{}", c.name));
     }
 
-    pub(crate) fn print_users(&self) {
+    pub fn print_labels(&self) {
+        for label in &self.labels {
+            println!(
+                "{} ({})",
+                label.name.style(label_style(label.color)),
+                label.color
+            );
+        }
+    }
+
+    /// The task's labels, colored and separated by spaces.
+    fn colored_labels(&self, task: &Task) -> String {
+        self.labels_of(task)
+            .iter()
+            .map(|l| l.name.style(label_style(l.color)).to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Like `colored_labels` but cut and padded to `width`, leaving out labels that don't fit.
+    fn labels_cell(&self, task: &Task, width: usize) -> String {
+        let mut cell = String::new();
+        let mut used = 0;
+        for label in self.labels_of(task) {
+            let separator = if used == 0 { 0 } else { 1 };
+            // Cutting a label shorter than two characters leaves just the ellipsis
+            if used + separator + 2 > width {
+                break;
+            }
+            let name = t(&label.name, width - used - separator);
+            let length = name.chars().count() + separator;
+            if separator == 1 {
+                cell.push(' ');
+            }
+            cell.push_str(&name.style(label_style(label.color)).to_string());
+            used += length;
+        }
+        format!("{}{}", cell, " ".repeat(width - used))
+    }
+
+    /// Lists the commits referencing the task, in the layout of `print_single_task`.
+    pub fn print_task_commits(&self, task_id: u64, commits: &[Commit]) {
+        let width = 60;
+        let commits = commits
+            .iter()
+            .filter(|c| c.references(task_id))
+            .collect::<Vec<_>>();
+        if commits.is_empty() {
+            return;
+        }
+        utils::print_line_left("Commits:", width);
+        for commit in commits {
+            let marker = if commit.fixes(task_id) {
+                " (fixes)"
+            } else {
+                ""
+            };
+            let line = format!(
+                "- {} {}{}",
+                commit.short_hash().yellow(),
+                t(&commit.subject, width - 12 - marker.len()),
+                marker
+            );
+            utils::print_line_left(&line, width);
+            utils::print_line_left(
+                &format!(
+                    "  {} on {}",
+                    commit.author_name,
+                    DateTime::from_timestamp(commit.at_utc, 0)
+                        .unwrap()
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                ),
+                width,
+            );
+        }
+        utils::print_divider(width);
+    }
+
+    pub fn print_time_report(&self, since: i64, grouping: TimeGrouping) {
+        let totals = self.time_report(since, grouping);
+        if totals.is_empty() {
+            println!("No time logged");
+            return;
+        }
+        let header = match grouping {
+            TimeGrouping::User => "User",
+            TimeGrouping::Task => "Task",
+        };
+        println!("{:<50}|{:>10}", header.bold(), "Time".bold());
+        println!("{}", "-".repeat(61));
+        for (name, seconds) in &totals {
+            println!(
+                "{:<50}|{:>10}",
+                t(name, 50),
+                utils::format_time_spent(*seconds)
+            );
+        }
+        println!("{}", "-".repeat(61));
+        let total = totals.iter().map(|(_, s)| s).sum::<i64>();
+        println!("{:<50}|{:>10}", "Total", utils::format_time_spent(total));
+    }
+
+    pub fn print_users(&self) {
         let users = self.get_users();
         for ele in users {
             match &ele.git_email {
@@ -368,16 +612,8 @@ impl Project {
             }
         }
     }
-    pub(crate) fn print_user_status(&self, user_id: u64) {
-        let mut user_tasks: Vec<&Task> = self
-            .tasks
-            .iter()
-            .filter(|x| x.assigned_to.iter().any(|u| u == &user_id))
-            .collect();
-
-        user_tasks.sort_by(|a, b| b.category.cmp(&a.category));
-
-        for task in &user_tasks {
+    pub fn print_user_status(&self, tasks: &[&Task]) {
+        for task in tasks {
             println!("{}", task.name.green());
             if !task.description.is_empty() {
                 Self::print_description(&task.description);
@@ -392,10 +628,229 @@ impl Project {
 
     fn print_description(description: &str) {
         println!("{}", "-".repeat(20));
-        println!(
-            "{}",
-            utils::format_description(description, 80).join("\n")
-        );
+        println!("{}", utils::format_description(description, 80).join("\n"));
         println!("{}", "-".repeat(20));
     }
+
+    /// Prints the history newest first like `git log`, optionally only that of one task.
+    pub fn print_history(&self, task_id: Option<u64>, limit: Option<usize>) {
+        let mut events = self
+            .history
+            .iter()
+            .rev()
+            .filter(|e| task_id.is_none() || e.task_id() == task_id)
+            .collect::<Vec<&HistoryEvent>>();
+        events.sort_by_key(|e| std::cmp::Reverse(e.at_utc));
+
+        for event in events.iter().take(limit.unwrap_or(usize::MAX)) {
+            println!("{}", format!("event {}", event.id).yellow());
+            println!("Author: {}", event.actor.as_deref().unwrap_or("unknown"));
+            println!(
+                "Date:   {}",
+                DateTime::from_timestamp(event.at_utc, 0)
+                    .unwrap()
+                    .with_timezone(&Local)
+                    .format("%a %b %e %H:%M:%S %Y %z")
+            );
+            println!();
+            println!("    {}", self.describe_event(event));
+            println!();
+        }
+    }
+
+    /// Name a label had before it was removed, as recorded in the history.
+    fn removed_label_name(&self, id: u64) -> Option<String> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|e| e.subject_id == id)
+            .filter(|e| {
+                matches!(
+                    e.operation,
+                    Operation::LabelAdded | Operation::LabelEdited | Operation::LabelRemoved
+                )
+            })
+            .find_map(|e| {
+                e.old_value
+                    .as_ref()
+                    .or(e.new_value.as_ref())?
+                    .get("name")?
+                    .as_str()
+                    .map(String::from)
+            })
+    }
+
+    fn describe_event(&self, event: &HistoryEvent) -> String {
+        let id = event.subject_id;
+        let old = event.old_value.as_ref();
+        let new = event.new_value.as_ref();
+        let text = |v: Option<&Value>| match v {
+            Some(Value::String(s)) => format!("\"{}\"", s),
+            Some(v) => v.to_string(),
+            None => "nothing".to_string(),
+        };
+        let text_of = |v: Option<&Value>| v.and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let field = |v: Option<&Value>, key: &str| text(v.and_then(|v| v.get(key)));
+        let category = |v: Option<&Value>| match v.and_then(|v| v.as_u64()) {
+            Some(c) => self.get_category_name(c).unwrap_or(c.to_string()),
+            None => text(v),
+        };
+        let seconds = |v: Option<&Value>| match v.and_then(|v| v.as_i64()) {
+            Some(s) => utils::format_time_spent(s),
+            None => text(v),
+        };
+        let label = |v: Option<&Value>| match v.and_then(|v| v.as_u64()) {
+            Some(l) => self
+                .get_label(l)
+                .map(|l| l.name.clone())
+                .or_else(|| self.removed_label_name(l))
+                .unwrap_or(l.to_string()),
+            None => text(v),
+        };
+        let user = |v: Option<&Value>| match v.and_then(|v| v.as_u64()) {
+            Some(u) => self.get_user(u).map(|u| u.name).unwrap_or(u.to_string()),
+            None => text(v),
+        };
+        let date = |v: Option<&Value>| match v.and_then(|v| v.as_i64()) {
+            Some(t) => DateTime::from_timestamp(t, 0)
+                .unwrap()
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            None => text(v),
+        };
+
+        match event.operation {
+            Operation::TaskAdded => format!("created task {} {}", id, text(new)),
+            Operation::TaskRenamed => {
+                format!("renamed task {} from {} to {}", id, text(old), text(new))
+            }
+            Operation::TaskDescriptionChanged => {
+                format!("changed the description of task {}", id)
+            }
+            Operation::TaskMoved => format!(
+                "moved task {} from {} to {}",
+                id,
+                category(old),
+                category(new)
+            ),
+            Operation::TaskArchived => format!("archived task {}", id),
+            Operation::TaskUnarchived => format!("restored archived task {}", id),
+            Operation::TaskRemoved => format!("removed task {} {}", id, field(old, "name")),
+            Operation::TaskAssigned => format!("assigned {} to task {}", user(new), id),
+            Operation::TaskUnassigned => format!("unassigned {} from task {}", user(old), id),
+            Operation::TaskDueDateSet => {
+                format!("set the due date of task {} to {}", id, date(new))
+            }
+            Operation::TaskDueDateCleared => format!("cleared the due date of task {}", id),
+            Operation::TaskLinked => format!("task {} now blocks task {}", id, text(new)),
+            Operation::TaskUnlinked => format!("task {} no longer blocks task {}", id, text(old)),
+            Operation::TaskParentChanged => match new.and_then(|v| v.as_u64()) {
+                Some(parent) => format!("made task {} a subtask of task {}", id, parent),
+                None => format!("made task {} a top level task", id),
+            },
+            Operation::TaskPrioritySet => {
+                format!("set the priority of task {} to {}", id, text_of(new))
+            }
+            Operation::TaskPriorityCleared => format!("cleared the priority of task {}", id),
+            Operation::TaskLabeled => format!("labeled task {} {}", id, label(new)),
+            Operation::TaskUnlabeled => format!("removed label {} from task {}", label(old), id),
+            Operation::TaskEstimateSet => format!(
+                "estimated task {} at {}",
+                id,
+                new.and_then(|v| serde_json::from_value::<Estimate>(v.clone()).ok())
+                    .map_or(text(new), |e| e.to_string())
+            ),
+            Operation::TaskEstimateCleared => format!("cleared the estimate of task {}", id),
+            Operation::TaskRecurrenceSet => format!(
+                "made task {} repeat {}",
+                id,
+                new.and_then(|v| serde_json::from_value::<Recurrence>(v.clone()).ok())
+                    .map_or(text(new), |r| r.to_string())
+            ),
+            Operation::TaskRecurrenceCleared => format!("stopped task {} from repeating", id),
+            Operation::TaskRecurred => format!("task {} came back as task {}", id, text(new)),
+            Operation::TimerStarted => format!("started tracking time on task {}", id),
+            Operation::TimerStopped => format!(
+                "stopped tracking time on task {} after {}",
+                id,
+                seconds(new)
+            ),
+            Operation::TimeLogged => format!("logged {} on task {}", seconds(new), id),
+            Operation::ChecklistItemAdded => {
+                format!("added checklist item {} to task {}", field(new, "name"), id)
+            }
+            Operation::ChecklistItemRemoved => format!(
+                "removed checklist item {} from task {}",
+                field(old, "name"),
+                id
+            ),
+            Operation::ChecklistItemChecked => {
+                format!("checked {} in task {}", field(new, "name"), id)
+            }
+            Operation::ChecklistItemUnchecked => {
+                format!("unchecked {} in task {}", field(new, "name"), id)
+            }
+            Operation::ChecklistItemRenamed => format!(
+                "renamed checklist item {} of task {} to {}",
+                field(old, "name"),
+                id,
+                field(new, "name")
+            ),
+            Operation::ChecklistItemMoved => format!(
+                "moved a checklist item of task {} from position {} to {}",
+                id,
+                text(old),
+                text(new)
+            ),
+            Operation::CategoryAdded => format!("added category {}", text(new)),
+            Operation::CategoryRenamed => {
+                format!("renamed category {} to {}", text(old), text(new))
+            }
+            Operation::CategoryRemoved => format!("removed category {}", text(old)),
+            Operation::CommitFixesCategorySet => match new {
+                Some(_) => format!("made commits move fixed tasks to {}", category(new)),
+                None => "made commits move fixed tasks to the first done category".to_string(),
+            },
+            Operation::LabelAdded => format!("added label {}", field(new, "name")),
+            Operation::LabelEdited => format!(
+                "edited label {}, now {} in {}",
+                field(old, "name"),
+                field(new, "name"),
+                text_of(new.and_then(|v| v.get("color")))
+            ),
+            Operation::LabelRemoved => format!("removed label {}", field(old, "name")),
+            Operation::UserAdded => format!("added user {}", field(new, "name")),
+            Operation::UserEdited => format!(
+                "edited user {}, now {}",
+                field(old, "name"),
+                field(new, "name")
+            ),
+            Operation::UserRemoved => format!("removed user {}", field(old, "name")),
+            Operation::ChangesUndone => format!("undid `{}`", text_of(new)),
+            Operation::ChangesRedone => format!("redid `{}`", text_of(new)),
+        }
+    }
+}
+
+fn priority_style(priority: Priority) -> Style {
+    match priority {
+        Priority::P0 => Style::new().red().bold(),
+        Priority::P1 => Style::new().yellow(),
+        Priority::P2 => Style::new().blue(),
+        Priority::P3 => Style::new().dimmed(),
+    }
+}
+
+fn label_style(color: LabelColor) -> Style {
+    match color {
+        LabelColor::Red => Style::new().red(),
+        LabelColor::Green => Style::new().green(),
+        LabelColor::Yellow => Style::new().yellow(),
+        LabelColor::Blue => Style::new().blue(),
+        LabelColor::Magenta => Style::new().magenta(),
+        LabelColor::Cyan => Style::new().cyan(),
+        LabelColor::White => Style::new().white(),
+        LabelColor::Gray => Style::new().bright_black(),
+    }
 }
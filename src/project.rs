@@ -1,17 +1,77 @@
-use crate::models::{Category, CheckListItem, Project, Task, User};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::commits::Commit;
+use crate::error::PiqoError;
+use crate::models::{
+    Category, CheckListItem, Estimate, HistoryEvent, Label, LabelColor, Operation, Priority,
+    Project, Recurrence, Task, TimeEntry, TimeGrouping, User,
+};
+use crate::schema;
 use crate::utils;
 
 impl Project {
-    pub(crate) fn new(name: String) -> Self {
+    pub fn new(name: String) -> Self {
         Project {
+            schema_version: schema::SCHEMA_VERSION,
             name,
             default_category: 0,
+            commit_fixes_category: None,
             categories: vec![],
+            labels: vec![],
             tasks: vec![],
             users: vec![],
+            time_entries: vec![],
+            history: vec![],
+            actor: None,
+            loaded_state: None,
         }
     }
 
+    /// Sets the git email changes are recorded under in the history.
+    pub fn set_actor(&mut self, actor: Option<String>) {
+        self.actor = actor;
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        operation: Operation,
+        subject_id: u64,
+        old_value: Option<Value>,
+        new_value: Option<Value>,
+    ) {
+        let id = utils::get_unused_event_id(self.history.iter().map(|e| e.id).collect());
+        self.history.push(HistoryEvent {
+            id,
+            at_utc: chrono::Utc::now().timestamp(),
+            actor: self.actor.clone(),
+            operation,
+            subject_id,
+            old_value,
+            new_value,
+        });
+    }
+
+    /// Replaces a field of a task, bumping `updated_at_utc` and recording the change.
+    fn change_task<V: Serialize + PartialEq>(
+        &mut self,
+        id: u64,
+        operation: Operation,
+        new: V,
+        field: impl FnOnce(&mut Task) -> &mut V,
+    ) -> Result<(), PiqoError> {
+        let task = self.task_mut(id)?;
+        let slot = field(task);
+        if *slot == new {
+            return Ok(());
+        }
+        let new_value = to_json(&new);
+        let old = std::mem::replace(slot, new);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(operation, id, to_json(&old), new_value);
+        Ok(())
+    }
+
     fn create_category(&self, name: &str) -> Category {
         let id = utils::get_unused_id(self.categories.iter().map(|i| i.id).collect());
         Category {
@@ -20,17 +80,20 @@ impl Project {
         }
     }
 
-    pub(crate) fn add_default_category(&mut self, name: &str) {
+    pub fn add_default_category(&mut self, name: &str) {
         let s = self.create_category(name);
         self.default_category = s.id;
+        self.record(Operation::CategoryAdded, s.id, None, to_json(&s.name));
         self.categories.push(s);
     }
 
-    pub(crate) fn add_category(&mut self, name: &str) {
-        self.categories.push(self.create_category(name))
+    pub fn add_category(&mut self, name: &str) {
+        let s = self.create_category(name);
+        self.record(Operation::CategoryAdded, s.id, None, to_json(&s.name));
+        self.categories.push(s)
     }
 
-    pub(crate) fn add_task(&mut self, name: String) -> u64 {
+    pub fn add_task(&mut self, name: String) -> u64 {
         let id = utils::get_unused_id(self.tasks.iter().map(|i| i.id).collect());
         let created_at_utc = chrono::Utc::now().timestamp();
         let updated_at_utc = chrono::Utc::now().timestamp();
@@ -47,209 +110,1135 @@ impl Project {
             due_date_utc: None,
             check_list: vec![],
             last_check_list_index: 0,
+            blocks: vec![],
+            parent_id: None,
+            priority: None,
+            labels: vec![],
+            estimate: None,
+            recurrence: None,
         };
+        self.record(Operation::TaskAdded, id, None, to_json(&task.name));
         self.tasks.push(task);
         id
     }
 
-    pub(crate) fn archive_task(&mut self, id: u64) {
+    fn task_mut(&mut self, id: u64) -> Result<&mut Task, PiqoError> {
         self.tasks
             .iter_mut()
             .find(|t| t.id == id)
-            .unwrap()
-            .archived_at_utc = Some(chrono::Utc::now().timestamp());
+            .ok_or(PiqoError::TaskNotFound(id))
     }
 
-    pub(crate) fn move_task(&mut self, task_id: u64, category_id: u64) {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == task_id)
-            .unwrap()
-            .category = category_id;
+    fn existing_task(&self, id: u64) -> Result<&Task, PiqoError> {
+        self.get_task(id).ok_or(PiqoError::TaskNotFound(id))
     }
 
-    pub(crate) fn remove_category(&mut self, id: u64) {
-        self.categories.retain_mut(|c| c.id != id)
+    /// Archives the task, archiving it again keeps the time it was first archived at.
+    ///
+    /// Returns the id of the next occurrence when a recurring task was archived.
+    pub fn archive_task(&mut self, id: u64) -> Result<Option<u64>, PiqoError> {
+        self.archive_task_inner(id, true)
+    }
+
+    /// Archives the task, creating the next occurrence of a recurring task only if `recur`.
+    pub(crate) fn archive_task_inner(
+        &mut self,
+        id: u64,
+        recur: bool,
+    ) -> Result<Option<u64>, PiqoError> {
+        if self.existing_task(id)?.is_archived() {
+            return Ok(None);
+        }
+        let now = Some(chrono::Utc::now().timestamp());
+        self.change_task(id, Operation::TaskArchived, now, |t| &mut t.archived_at_utc)?;
+        if !recur {
+            return Ok(None);
+        }
+        self.recur(id)
     }
 
-    pub(crate) fn get_category(&self, id: u64) -> Option<&Category> {
+    pub fn unarchive_task(&mut self, id: u64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskUnarchived, None, |t| {
+            &mut t.archived_at_utc
+        })
+    }
+
+    /// Moves the task to the category.
+    ///
+    /// Returns the id of the next occurrence when a recurring task was moved to a done
+    /// category.
+    pub fn move_task(&mut self, task_id: u64, category_id: u64) -> Result<Option<u64>, PiqoError> {
+        self.move_task_inner(task_id, category_id, true)
+    }
+
+    /// Moves the task, creating the next occurrence of a recurring task only if `recur`.
+    pub(crate) fn move_task_inner(
+        &mut self,
+        task_id: u64,
+        category_id: u64,
+        recur: bool,
+    ) -> Result<Option<u64>, PiqoError> {
+        if self.get_category(category_id).is_none() {
+            return Err(PiqoError::CategoryNotFound(category_id.to_string()));
+        }
+        let was_done = self.is_done_category(self.existing_task(task_id)?.category);
+        self.change_task(task_id, Operation::TaskMoved, category_id, |t| {
+            &mut t.category
+        })?;
+        if !recur || was_done || !self.is_done_category(category_id) {
+            return Ok(None);
+        }
+        self.recur(task_id)
+    }
+
+    pub fn set_task_recurrence(
+        &mut self,
+        id: u64,
+        recurrence: Recurrence,
+    ) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskRecurrenceSet, Some(recurrence), |t| {
+            &mut t.recurrence
+        })
+    }
+
+    pub fn clear_task_recurrence(&mut self, id: u64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskRecurrenceCleared, None, |t| {
+            &mut t.recurrence
+        })
+    }
+
+    /// Creates the next occurrence of a finished recurring task and hands the recurrence
+    /// over to it, returning its id.
+    ///
+    /// The occurrence starts in the default category with the same assignees, labels,
+    /// priority and estimate, an unchecked copy of the checklist and the next due date.
+    fn recur(&mut self, id: u64) -> Result<Option<u64>, PiqoError> {
+        let task = self.existing_task(id)?.clone();
+        let Some(recurrence) = task.recurrence else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().timestamp();
+        let due_date = recurrence.next_after(task.due_date_utc.unwrap_or(now), now);
+
+        let next = self.add_task(task.name.clone());
+        if !task.description.is_empty() {
+            self.edit_task_description(next, task.description.clone())?;
+        }
+        self.set_task_due_date(next, due_date)?;
+        if let Some(priority) = task.priority {
+            self.set_task_priority(next, priority)?;
+        }
+        if let Some(estimate) = task.estimate {
+            self.set_task_estimate(next, estimate)?;
+        }
+        if task.parent_id.is_some() {
+            self.set_parent(next, task.parent_id)?;
+        }
+        for label in &task.labels {
+            self.label_task(next, *label)?;
+        }
+        for item in &task.check_list {
+            self.add_checklist_item(next, item.name.clone())?;
+        }
+        for user in &task.assigned_to {
+            self.assign_task(*user, next)?;
+        }
+
+        self.clear_task_recurrence(id)?;
+        self.record(Operation::TaskRecurred, id, None, to_json(&next));
+        self.set_task_recurrence(next, recurrence)?;
+        Ok(Some(next))
+    }
+
+    pub fn remove_category(&mut self, id: u64) {
+        let Some(category) = self.get_category(id) else {
+            return;
+        };
+        let old = to_json(&category.name);
+        if self.commit_fixes_category == Some(id) {
+            self.commit_fixes_category = None;
+            self.record(Operation::CommitFixesCategorySet, 0, to_json(&id), None);
+        }
+        self.categories.retain_mut(|c| c.id != id);
+        self.record(Operation::CategoryRemoved, id, old, None);
+    }
+
+    /// Sets the category `sync_commits` moves fixed tasks to, `None` goes back to the
+    /// first done category.
+    pub fn set_commit_fixes_category(&mut self, category: Option<u64>) -> Result<(), PiqoError> {
+        if let Some(id) = category
+            && self.get_category(id).is_none()
+        {
+            return Err(PiqoError::CategoryNotFound(id.to_string()));
+        }
+        if self.commit_fixes_category == category {
+            return Ok(());
+        }
+        let old = std::mem::replace(&mut self.commit_fixes_category, category);
+        self.record(
+            Operation::CommitFixesCategorySet,
+            0,
+            to_json(&old),
+            to_json(&category),
+        );
+        Ok(())
+    }
+
+    /// The category fixed tasks move to, `None` without a configured or done category.
+    pub fn get_commit_fixes_category(&self) -> Option<u64> {
+        self.commit_fixes_category.or_else(|| {
+            self.categories
+                .iter()
+                .map(|c| c.id)
+                .find(|c| self.is_done_category(*c))
+        })
+    }
+
+    /// The category started tasks move to: the one named like "In Progress", otherwise the
+    /// first that is neither the default nor done.
+    pub fn get_in_progress_category(&self) -> Option<u64> {
+        let named = self.categories.iter().find(|c| {
+            IN_PROGRESS_CATEGORY_NAMES
+                .iter()
+                .any(|name| c.name.eq_ignore_ascii_case(name))
+        });
+        named
+            .or_else(|| {
+                self.categories
+                    .iter()
+                    .find(|c| c.id != self.default_category && !self.is_done_category(c.id))
+            })
+            .map(|c| c.id)
+    }
+
+    /// Moves the tasks that commits say they fix to `category`, returning the moved tasks
+    /// with the fixing commit.
+    ///
+    /// Only commits made after the task last moved category count, so a task reopened after
+    /// its fix stays open while other edits don't hide the fix.
+    pub fn sync_commits(
+        &mut self,
+        commits: &[Commit],
+        category: u64,
+    ) -> Result<Vec<(u64, String)>, PiqoError> {
+        let mut moved = Vec::new();
+        for commit in commits {
+            for task_ref in commit.refs.iter().filter(|r| r.fixes) {
+                let Some(task) = self.get_task(task_ref.task_id) else {
+                    continue;
+                };
+                if task.is_archived()
+                    || task.category == category
+                    || self.last_moved_at(task.id) >= Some(commit.at_utc)
+                {
+                    continue;
+                }
+                self.move_task(task_ref.task_id, category)?;
+                moved.push((task_ref.task_id, commit.hash.clone()));
+            }
+        }
+        Ok(moved)
+    }
+
+    /// When the task last moved to another category, going by the history.
+    fn last_moved_at(&self, task_id: u64) -> Option<i64> {
+        self.history
+            .iter()
+            .filter(|e| e.operation == Operation::TaskMoved && e.subject_id == task_id)
+            .map(|e| e.at_utc)
+            .max()
+    }
+
+    pub fn get_category(&self, id: u64) -> Option<&Category> {
         self.categories.iter().find(|c| c.id == id)
     }
 
-    pub(crate) fn get_category_name(&self, id: u64) -> Option<String> {
+    /// Resolves a category by its name (case insensitive) or its id.
+    pub fn resolve_category(&self, category: &str) -> Result<u64, PiqoError> {
+        if let Some(c) = self.get_category_by_name(category) {
+            return Ok(c.id);
+        }
+        match category
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| self.get_category(id))
+        {
+            Some(c) => Ok(c.id),
+            None => Err(PiqoError::CategoryNotFound(category.to_string())),
+        }
+    }
+
+    /// Resolves a user by git email, id or name, ignoring case of emails and names.
+    pub fn resolve_user(&self, user: &str) -> Result<u64, PiqoError> {
+        let by_email = self.users.iter().find(|u| {
+            u.git_email
+                .as_deref()
+                .is_some_and(|e| e.eq_ignore_ascii_case(user))
+        });
+        if let Some(u) = by_email {
+            return Ok(u.id);
+        }
+        if let Some(u) = user.parse::<u64>().ok().and_then(|id| self.get_user(id)) {
+            return Ok(u.id);
+        }
+        match self
+            .users
+            .iter()
+            .find(|u| u.name.eq_ignore_ascii_case(user))
+        {
+            Some(u) => Ok(u.id),
+            None => Err(PiqoError::UserNotFound(user.to_string())),
+        }
+    }
+
+    /// Resolves a label by its name (case insensitive) or its id.
+    pub fn resolve_label(&self, label: &str) -> Result<u64, PiqoError> {
+        if let Some(l) = self.get_label_by_name(label) {
+            return Ok(l.id);
+        }
+        match label.parse::<u64>().ok().and_then(|id| self.get_label(id)) {
+            Some(l) => Ok(l.id),
+            None => Err(PiqoError::LabelNotFound(label.to_string())),
+        }
+    }
+
+    pub fn get_category_by_name(&self, name: &str) -> Option<&Category> {
+        self.categories
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_category_name(&self, id: u64) -> Option<String> {
         self.categories
             .iter()
             .find(|c| c.id == id)
             .map(|c| c.name.clone())
     }
 
-    pub(crate) fn get_users(&self) -> &Vec<User> {
+    pub fn get_users(&self) -> &Vec<User> {
         &self.users
     }
 
-    pub(crate) fn remove_user(&mut self, ele: &User) {
-        self.tasks
-            .retain_mut(|t| t.assigned_to.iter().all(|u| u != &ele.id));
-        self.users.retain_mut(|u| u.id != ele.id);
+    /// Adds a label, picking the next unused color when none is given.
+    pub fn add_label(&mut self, name: &str, color: Option<LabelColor>) -> Result<u64, PiqoError> {
+        if self.get_label_by_name(name).is_some() {
+            return Err(PiqoError::InvalidInput(format!(
+                "label with name {} already exists",
+                name
+            )));
+        }
+        let color = color.unwrap_or_else(|| {
+            LabelColor::ALL
+                .into_iter()
+                .find(|c| self.labels.iter().all(|l| l.color != *c))
+                .unwrap_or(LabelColor::ALL[self.labels.len() % LabelColor::ALL.len()])
+        });
+        let label = Label {
+            id: utils::get_unused_id(self.labels.iter().map(|l| l.id).collect()),
+            name: name.to_string(),
+            color,
+        };
+        let id = label.id;
+        self.record(Operation::LabelAdded, id, None, to_json(&label));
+        self.labels.push(label);
+        Ok(id)
+    }
+
+    pub fn edit_label(
+        &mut self,
+        id: u64,
+        name: Option<&str>,
+        color: Option<LabelColor>,
+    ) -> Result<(), PiqoError> {
+        if let Some(name) = name
+            && self.get_label_by_name(name).is_some_and(|l| l.id != id)
+        {
+            return Err(PiqoError::InvalidInput(format!(
+                "label with name {} already exists",
+                name
+            )));
+        }
+        let label = self
+            .labels
+            .iter_mut()
+            .find(|l| l.id == id)
+            .ok_or(PiqoError::LabelNotFound(id.to_string()))?;
+        let old = to_json(label);
+        if let Some(name) = name {
+            label.name = name.to_string();
+        }
+        if let Some(color) = color {
+            label.color = color;
+        }
+        let new = to_json(label);
+        if old != new {
+            self.record(Operation::LabelEdited, id, old, new);
+        }
+        Ok(())
     }
 
-    pub(crate) fn assign_task(&mut self, user_id: u64, task_id: u64) {
-        let already_assigned = self
+    /// Removes the label, untagging the tasks tagged with it.
+    pub fn remove_label(&mut self, id: u64) -> Result<(), PiqoError> {
+        let label = self
+            .get_label(id)
+            .cloned()
+            .ok_or(PiqoError::LabelNotFound(id.to_string()))?;
+        let tagged = self
             .tasks
             .iter()
-            .find(|t| t.id == task_id)
-            .unwrap()
-            .assigned_to
+            .filter(|t| t.labels.contains(&id))
+            .map(|t| t.id)
+            .collect::<Vec<u64>>();
+        for task_id in tagged {
+            self.unlabel_task(task_id, id)?;
+        }
+        self.labels.retain(|l| l.id != id);
+        self.record(Operation::LabelRemoved, id, to_json(&label), None);
+        Ok(())
+    }
+
+    pub fn get_label(&self, id: u64) -> Option<&Label> {
+        self.labels.iter().find(|l| l.id == id)
+    }
+
+    pub fn get_label_by_name(&self, name: &str) -> Option<&Label> {
+        self.labels
             .iter()
-            .any(|u| u == &user_id);
-        if already_assigned {
-            return;
+            .find(|l| l.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Labels of the task in the order of the project's labels.
+    pub fn labels_of(&self, task: &Task) -> Vec<&Label> {
+        self.labels
+            .iter()
+            .filter(|l| task.labels.contains(&l.id))
+            .collect()
+    }
+
+    pub fn label_task(&mut self, task_id: u64, label_id: u64) -> Result<(), PiqoError> {
+        if self.get_label(label_id).is_none() {
+            return Err(PiqoError::LabelNotFound(label_id.to_string()));
         }
+        let task = self.task_mut(task_id)?;
+        if task.labels.contains(&label_id) {
+            return Ok(());
+        }
+        task.labels.push(label_id);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskLabeled, task_id, None, to_json(&label_id));
+        Ok(())
+    }
 
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == task_id)
-            .unwrap()
-            .assigned_to
-            .push(user_id);
+    pub fn unlabel_task(&mut self, task_id: u64, label_id: u64) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
+        if !task.labels.contains(&label_id) {
+            return Ok(());
+        }
+        task.labels.retain(|l| *l != label_id);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskUnlabeled, task_id, to_json(&label_id), None);
+        Ok(())
     }
 
-    pub(crate) fn unassign_task(&mut self, user_id: u64, task_id: u64) {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == task_id)
-            .unwrap()
-            .assigned_to
-            .retain(|u| u != &user_id);
+    /// Removes the user, unassigning them from their tasks.
+    pub fn remove_user(&mut self, ele: &User) {
+        let assigned_tasks = self
+            .get_tasks_assigned_to(ele.id)
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<u64>>();
+        for id in assigned_tasks {
+            // The tasks were just looked up, they exist
+            let _ = self.unassign_task(ele.id, id);
+        }
+        if let Some(user) = self.get_user(ele.id) {
+            self.users.retain_mut(|u| u.id != ele.id);
+            self.record(Operation::UserRemoved, user.id, to_json(&user), None);
+        }
     }
 
-    pub(crate) fn add_user(&mut self, name: &str, git_email: &str) {
+    pub fn assign_task(&mut self, user_id: u64, task_id: u64) -> Result<(), PiqoError> {
+        if self.get_user(user_id).is_none() {
+            return Err(PiqoError::UserNotFound(user_id.to_string()));
+        }
+        let task = self.task_mut(task_id)?;
+        if task.assigned_to.contains(&user_id) {
+            return Ok(());
+        }
+        task.assigned_to.push(user_id);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskAssigned, task_id, None, to_json(&user_id));
+        Ok(())
+    }
+
+    pub fn unassign_task(&mut self, user_id: u64, task_id: u64) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
+        if !task.assigned_to.contains(&user_id) {
+            return Ok(());
+        }
+        task.assigned_to.retain(|u| u != &user_id);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskUnassigned, task_id, to_json(&user_id), None);
+        Ok(())
+    }
+
+    pub fn add_user(&mut self, name: &str, git_email: &str) {
         let id = utils::get_unused_id(self.users.iter().map(|u| u.id).collect());
 
-        self.users.push(User {
+        let user = User {
             id,
             name: name.to_string(),
             git_email: Some(git_email.to_string()),
-        });
+        };
+        self.record(Operation::UserAdded, id, None, to_json(&user));
+        self.users.push(user);
     }
 
-    pub(crate) fn get_user(&self, id: u64) -> Option<User> {
+    pub fn get_user(&self, id: u64) -> Option<User> {
         self.users.iter().find(|u| u.id == id).map(|u| u.to_owned())
     }
 
-    pub(crate) fn get_assigned_users(&self, id: u64) -> Vec<User> {
-        let task = self.tasks.iter().find(|t| t.id == id).unwrap();
-        let mut users = Vec::new();
-        for user_id in &task.assigned_to {
-            users.push(self.get_user(*user_id).unwrap().to_owned());
-        }
-        users
+    pub fn get_assigned_users(&self, id: u64) -> Result<Vec<User>, PiqoError> {
+        Ok(self.users_of(self.existing_task(id)?))
+    }
+
+    pub fn users_of(&self, task: &Task) -> Vec<User> {
+        task.assigned_to
+            .iter()
+            .filter_map(|id| self.get_user(*id))
+            .collect()
+    }
+
+    pub fn get_unarchived_tasks(&self) -> Vec<&Task> {
+        self.tasks.iter().filter(|t| !t.is_archived()).collect()
     }
-    pub(crate) fn get_unarchived_tasks(&self) -> Vec<&Task> {
+
+    pub fn get_archived_tasks(&self) -> Vec<&Task> {
+        self.tasks.iter().filter(|t| t.is_archived()).collect()
+    }
+
+    /// Archived tasks that were archived before the unix timestamp `cutoff`.
+    pub fn get_tasks_archived_before(&self, cutoff: i64) -> Vec<&Task> {
         self.tasks
             .iter()
-            .filter(|t| t.archived_at_utc.is_none())
+            .filter(|t| t.archived_at_utc.is_some_and(|at| at < cutoff))
             .collect()
     }
 
-    pub(crate) fn edit_category(&mut self, category_id: u64, new_name: &str) {
-        self.categories
+    pub fn edit_category(&mut self, category_id: u64, new_name: &str) -> Result<(), PiqoError> {
+        let category = self
+            .categories
             .iter_mut()
             .find(|c| c.id == category_id)
-            .unwrap()
-            .name = new_name.to_string();
+            .ok_or(PiqoError::CategoryNotFound(category_id.to_string()))?;
+        if category.name == new_name {
+            return Ok(());
+        }
+        let old = std::mem::replace(&mut category.name, new_name.to_string());
+        self.record(
+            Operation::CategoryRenamed,
+            category_id,
+            to_json(&old),
+            to_json(&new_name),
+        );
+        Ok(())
     }
 
-    pub(crate) fn edit_task_name(&mut self, id: u64, new_name: String) {
-        self.tasks.iter_mut().find(|t| t.id == id).unwrap().name = new_name;
+    pub fn edit_task_name(&mut self, id: u64, new_name: String) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskRenamed, new_name, |t| &mut t.name)
     }
 
-    pub(crate) fn edit_task_description(&mut self, id: u64, new_description: String) {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == id)
-            .unwrap()
-            .description = new_description;
+    pub fn edit_task_description(
+        &mut self,
+        id: u64,
+        new_description: String,
+    ) -> Result<(), PiqoError> {
+        self.change_task(
+            id,
+            Operation::TaskDescriptionChanged,
+            new_description,
+            |t| &mut t.description,
+        )
     }
 
-    pub(crate) fn remove_task(&mut self, id: u64) {
-        self.tasks.retain(|t| t.id != id)
+    pub fn remove_task(&mut self, id: u64) {
+        let Some(task) = self.get_task(id) else {
+            return;
+        };
+        let old = to_json(task);
+        let parent = task.parent_id;
+        let blockers = self
+            .get_blockers(id)
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<u64>>();
+        for blocker in blockers {
+            // Both tasks exist, unlinking can't fail
+            let _ = self.unlink_tasks(blocker, id);
+        }
+        // Subtasks move up to the parent of the removed task
+        let subtasks = self
+            .get_subtasks(id)
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<u64>>();
+        for subtask in subtasks {
+            let _ = self.change_task(subtask, Operation::TaskParentChanged, parent, |t| {
+                &mut t.parent_id
+            });
+        }
+        self.tasks.retain(|t| t.id != id);
+        self.record(Operation::TaskRemoved, id, old, None);
     }
 
-    pub(crate) fn get_user_by_email(&self, email: &str) -> Option<&User> {
+    pub fn get_user_by_email(&self, email: &str) -> Option<&User> {
         self.users
             .iter()
-            .find(|u| u.git_email.as_ref().unwrap() == email)
+            .find(|u| u.git_email.as_deref() == Some(email))
+    }
+
+    pub fn get_task(&self, id: u64) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
     }
 
-    pub(crate) fn get_task_description(&self, id: u64) -> &str {
+    pub fn get_tasks_in_category(&self, category_id: u64) -> Vec<&Task> {
         self.tasks
             .iter()
-            .find(|t| t.id == id)
-            .unwrap()
-            .description
-            .as_str()
+            .filter(|t| t.category == category_id)
+            .collect()
     }
 
-    pub(crate) fn edit_user(&mut self, id: u64, name: &str, email: Option<String>) {
-        self.users.iter_mut().find(|u| u.id == id).unwrap().name = name.to_string();
-        self.users
+    pub fn get_tasks_assigned_to(&self, user_id: u64) -> Vec<&Task> {
+        self.tasks
+            .iter()
+            .filter(|t| t.assigned_to.contains(&user_id))
+            .collect()
+    }
+
+    pub fn get_task_description(&self, id: u64) -> Result<&str, PiqoError> {
+        Ok(self.existing_task(id)?.description.as_str())
+    }
+
+    pub fn edit_user(
+        &mut self,
+        id: u64,
+        name: &str,
+        email: Option<String>,
+    ) -> Result<(), PiqoError> {
+        let user = self
+            .users
             .iter_mut()
             .find(|u| u.id == id)
-            .unwrap()
-            .git_email = email;
+            .ok_or(PiqoError::UserNotFound(id.to_string()))?;
+        if user.name == name && user.git_email == email {
+            return Ok(());
+        }
+        let old = to_json(user);
+        user.name = name.to_string();
+        user.git_email = email;
+        let new = to_json(user);
+        self.record(Operation::UserEdited, id, old, new);
+        Ok(())
     }
 
-    pub(crate) fn add_checklist_item(&mut self, task_id: u64, checklist_item_name: String) {
-        let task = self.tasks.iter_mut().find(|t| t.id == task_id).unwrap();
+    pub fn add_checklist_item(
+        &mut self,
+        task_id: u64,
+        checklist_item_name: String,
+    ) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
 
-        let next_index = task.last_check_list_index + 1;
+        // Merged branches can leave items beyond the last index, each having added some
+        let next_index = task
+            .check_list
+            .iter()
+            .map(|c| c.index)
+            .fold(task.last_check_list_index, u64::max)
+            + 1;
 
-        task.check_list.push(CheckListItem {
+        let item = CheckListItem {
             index: next_index,
             name: checklist_item_name,
             checked: false,
-        });
+        };
+        let new = to_json(&item);
+        task.check_list.push(item);
         task.last_check_list_index = next_index;
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::ChecklistItemAdded, task_id, None, new);
+        Ok(())
     }
 
-    pub(crate) fn remove_checklist_item(&mut self, task_id: u64, check_list_index: u64) {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.id == task_id)
-            .unwrap()
+    pub fn remove_checklist_item(
+        &mut self,
+        task_id: u64,
+        check_list_index: u64,
+    ) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
+        let Some(position) = task
             .check_list
-            .retain(|c| c.index != check_list_index);
+            .iter()
+            .position(|c| c.index == check_list_index)
+        else {
+            return Ok(());
+        };
+        let old = to_json(&task.check_list.remove(position));
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::ChecklistItemRemoved, task_id, old, None);
+        Ok(())
     }
-    pub(crate) fn get_task_checklist(&self, task_id: u64) -> Vec<CheckListItem> {
-        self.tasks
+
+    /// Checks or unchecks the checklist item with the given index.
+    pub fn set_checklist_item_checked(
+        &mut self,
+        task_id: u64,
+        check_list_index: u64,
+        checked: bool,
+    ) -> Result<(), PiqoError> {
+        let operation = match checked {
+            true => Operation::ChecklistItemChecked,
+            false => Operation::ChecklistItemUnchecked,
+        };
+        self.change_checklist_item(task_id, check_list_index, operation, |c| {
+            c.checked = checked
+        })
+    }
+
+    pub fn rename_checklist_item(
+        &mut self,
+        task_id: u64,
+        check_list_index: u64,
+        name: String,
+    ) -> Result<(), PiqoError> {
+        self.change_checklist_item(
+            task_id,
+            check_list_index,
+            Operation::ChecklistItemRenamed,
+            |c| c.name = name,
+        )
+    }
+
+    /// Moves the checklist item to `position`, counting from 1, keeping its index.
+    pub fn move_checklist_item(
+        &mut self,
+        task_id: u64,
+        check_list_index: u64,
+        position: usize,
+    ) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
+        let from = checklist_position(task, check_list_index)?;
+        let to = position.clamp(1, task.check_list.len()) - 1;
+        if from == to {
+            return Ok(());
+        }
+        let item = task.check_list.remove(from);
+        task.check_list.insert(to, item);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(
+            Operation::ChecklistItemMoved,
+            task_id,
+            to_json(&(from + 1)),
+            to_json(&(to + 1)),
+        );
+        Ok(())
+    }
+
+    /// Changes a checklist item, recording it before and after if anything changed.
+    fn change_checklist_item(
+        &mut self,
+        task_id: u64,
+        check_list_index: u64,
+        operation: Operation,
+        change: impl FnOnce(&mut CheckListItem),
+    ) -> Result<(), PiqoError> {
+        let task = self.task_mut(task_id)?;
+        let position = checklist_position(task, check_list_index)?;
+        let item = &mut task.check_list[position];
+        let old = to_json(item);
+        change(item);
+        let new = to_json(item);
+        if old == new {
+            return Ok(());
+        }
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(operation, task_id, old, new);
+        Ok(())
+    }
+
+    pub fn set_task_priority(&mut self, id: u64, priority: Priority) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskPrioritySet, Some(priority), |t| {
+            &mut t.priority
+        })
+    }
+
+    pub fn clear_task_priority(&mut self, id: u64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskPriorityCleared, None, |t| {
+            &mut t.priority
+        })
+    }
+
+    pub fn set_task_estimate(&mut self, id: u64, estimate: Estimate) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskEstimateSet, Some(estimate), |t| {
+            &mut t.estimate
+        })
+    }
+
+    pub fn clear_task_estimate(&mut self, id: u64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskEstimateCleared, None, |t| {
+            &mut t.estimate
+        })
+    }
+
+    /// Starts tracking time on the task for the actor, stopping what they tracked before.
+    ///
+    /// Returns the stopped entry, if any.
+    pub fn start_timer(&mut self, task_id: u64) -> Result<Option<TimeEntry>, PiqoError> {
+        self.existing_task(task_id)?;
+        if let Some(running) = self.get_running_timer()
+            && running.task_id == task_id
+        {
+            return Err(PiqoError::InvalidInput(format!(
+                "already tracking time on task {}",
+                task_id
+            )));
+        }
+        let stopped = self.stop_timer()?;
+        let entry = TimeEntry {
+            id: utils::get_unused_event_id(self.time_entries.iter().map(|e| e.id).collect()),
+            task_id,
+            actor: self.actor.clone(),
+            started_at_utc: chrono::Utc::now().timestamp(),
+            ended_at_utc: None,
+        };
+        self.record(Operation::TimerStarted, task_id, None, to_json(&entry.id));
+        self.time_entries.push(entry);
+        Ok(stopped)
+    }
+
+    /// Stops the actor's running time entry, returning it if there was one.
+    pub fn stop_timer(&mut self) -> Result<Option<TimeEntry>, PiqoError> {
+        let now = chrono::Utc::now().timestamp();
+        let actor = self.actor.clone();
+        let Some(entry) = self
+            .time_entries
+            .iter_mut()
+            .find(|e| e.ended_at_utc.is_none() && e.actor == actor)
+        else {
+            return Ok(None);
+        };
+        entry.ended_at_utc = Some(now);
+        let entry = entry.clone();
+        self.record(
+            Operation::TimerStopped,
+            entry.task_id,
+            None,
+            to_json(&entry.seconds(now)),
+        );
+        Ok(Some(entry))
+    }
+
+    /// Logs time the actor spent on the task, ending now.
+    pub fn log_time(&mut self, task_id: u64, seconds: i64) -> Result<(), PiqoError> {
+        self.existing_task(task_id)?;
+        let now = chrono::Utc::now().timestamp();
+        let entry = TimeEntry {
+            id: utils::get_unused_event_id(self.time_entries.iter().map(|e| e.id).collect()),
+            task_id,
+            actor: self.actor.clone(),
+            started_at_utc: now - seconds,
+            ended_at_utc: Some(now),
+        };
+        self.time_entries.push(entry);
+        self.record(Operation::TimeLogged, task_id, None, to_json(&seconds));
+        Ok(())
+    }
+
+    /// The time entry the actor is currently tracking time with.
+    pub fn get_running_timer(&self) -> Option<&TimeEntry> {
+        self.time_entries
             .iter()
-            .find(|t| t.id == task_id)
-            .unwrap()
-            .check_list
+            .find(|e| e.ended_at_utc.is_none() && e.actor == self.actor)
+    }
+
+    /// Tasks the actor is working on: the one they track time on, then the ones assigned
+    /// to them that left the default category but aren't done yet.
+    pub fn get_tasks_in_progress(&self) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self
+            .get_running_timer()
+            .and_then(|e| self.get_task(e.task_id))
+            .into_iter()
+            .collect();
+        let user = self
+            .actor
+            .as_deref()
+            .and_then(|email| self.get_user_by_email(email));
+        if let Some(user) = user {
+            for task in self.get_tasks_assigned_to(user.id) {
+                let in_progress = !task.is_archived()
+                    && task.category != self.default_category
+                    && !self.is_done_category(task.category);
+                if in_progress && !tasks.iter().any(|t| t.id == task.id) {
+                    tasks.push(task);
+                }
+            }
+        }
+        tasks
+    }
+
+    /// Seconds logged on the task by everyone, running entries count up to now.
+    pub fn get_time_spent(&self, task_id: u64) -> i64 {
+        let now = chrono::Utc::now().timestamp();
+        self.time_entries
             .iter()
-            .map(|c| c.to_owned())
-            .collect()
+            .filter(|e| e.task_id == task_id)
+            .map(|e| e.seconds(now))
+            .sum()
+    }
+
+    /// Seconds logged since `since`, summed up per user or task, most time first.
+    ///
+    /// Entries that started before `since` only count with the time after it.
+    pub fn time_report(&self, since: i64, grouping: TimeGrouping) -> Vec<(String, i64)> {
+        let now = chrono::Utc::now().timestamp();
+        let mut totals: Vec<(String, i64)> = Vec::new();
+        for entry in &self.time_entries {
+            let end = entry.ended_at_utc.unwrap_or(now);
+            if end <= since {
+                continue;
+            }
+            let seconds = end - entry.started_at_utc.max(since);
+            let key = match grouping {
+                TimeGrouping::User => match entry.actor.as_deref() {
+                    Some(email) => self
+                        .get_user_by_email(email)
+                        .map(|u| u.name.clone())
+                        .unwrap_or(email.to_string()),
+                    None => "unknown".to_string(),
+                },
+                TimeGrouping::Task => match self.get_task(entry.task_id) {
+                    Some(task) => format!("{} {}", task.id, task.name),
+                    None => format!("{} (removed)", entry.task_id),
+                },
+            };
+            match totals.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, total)) => *total += seconds,
+                None => totals.push((key, seconds)),
+            }
+        }
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        totals
+    }
+
+    pub fn get_task_checklist(&self, task_id: u64) -> Result<Vec<CheckListItem>, PiqoError> {
+        Ok(self.existing_task(task_id)?.check_list.to_owned())
+    }
+
+    pub fn set_task_due_date(&mut self, id: u64, due_date: i64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskDueDateSet, Some(due_date), |t| {
+            &mut t.due_date_utc
+        })
+    }
+
+    pub fn clear_task_due_date(&mut self, id: u64) -> Result<(), PiqoError> {
+        self.change_task(id, Operation::TaskDueDateCleared, None, |t| {
+            &mut t.due_date_utc
+        })
+    }
+
+    /// Makes `blocker` block `blocked`, refusing links that would create a cycle.
+    pub fn link_tasks(&mut self, blocker: u64, blocked: u64) -> Result<(), PiqoError> {
+        self.existing_task(blocked)?;
+        if self.existing_task(blocker)?.blocks.contains(&blocked) {
+            return Ok(());
+        }
+        if let Some(mut cycle) = self.dependency_path(blocked, blocker) {
+            cycle.insert(0, blocker);
+            return Err(PiqoError::DependencyCycle(cycle));
+        }
+        let task = self.task_mut(blocker)?;
+        task.blocks.push(blocked);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskLinked, blocker, None, to_json(&blocked));
+        Ok(())
+    }
+
+    pub fn unlink_tasks(&mut self, blocker: u64, blocked: u64) -> Result<(), PiqoError> {
+        let task = self.task_mut(blocker)?;
+        if !task.blocks.contains(&blocked) {
+            return Ok(());
+        }
+        task.blocks.retain(|b| *b != blocked);
+        task.updated_at_utc = chrono::Utc::now().timestamp();
+        self.record(Operation::TaskUnlinked, blocker, to_json(&blocked), None);
+        Ok(())
+    }
+
+    /// Returns the chain of tasks through which `from` blocks `to`, if it does.
+    fn dependency_path(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        let mut stack = vec![vec![from]];
+        let mut seen = vec![from];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            let Some(task) = self.get_task(last) else {
+                continue;
+            };
+            for next in &task.blocks {
+                if !seen.contains(next) {
+                    seen.push(*next);
+                    let mut next_path = path.clone();
+                    next_path.push(*next);
+                    stack.push(next_path);
+                }
+            }
+        }
+        None
+    }
+
+    /// Makes the task a subtask of `parent`, or a top level task with `None`.
+    pub fn set_parent(&mut self, id: u64, parent: Option<u64>) -> Result<(), PiqoError> {
+        self.existing_task(id)?;
+        if let Some(parent) = parent {
+            self.existing_task(parent)?;
+            if parent == id || self.get_ancestors(parent).contains(&id) {
+                return Err(PiqoError::InvalidInput(format!(
+                    "task {} can't be a subtask of its own subtask {}",
+                    id, parent
+                )));
+            }
+        }
+        self.change_task(id, Operation::TaskParentChanged, parent, |t| {
+            &mut t.parent_id
+        })
     }
 
-    pub(crate) fn set_task_due_date(&mut self, id: u64, due_date: i64) {
+    /// Parent, grandparent and so on of the task.
+    fn get_ancestors(&self, id: u64) -> Vec<u64> {
+        let mut ancestors = Vec::new();
+        let mut current = self.get_task(id).and_then(|t| t.parent_id);
+        while let Some(parent) = current {
+            if ancestors.contains(&parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = self.get_task(parent).and_then(|t| t.parent_id);
+        }
+        ancestors
+    }
+
+    pub fn get_subtasks(&self, id: u64) -> Vec<&Task> {
         self.tasks
-            .iter_mut()
-            .find(|t| t.id == id)
-            .unwrap()
-            .due_date_utc = Some(due_date);
+            .iter()
+            .filter(|t| t.parent_id == Some(id))
+            .collect()
+    }
+
+    /// Percentage of the subtasks that are finished, subtasks with subtasks of their own
+    /// count with their own progress. `None` for tasks without subtasks.
+    pub fn get_subtask_progress(&self, id: u64) -> Option<u64> {
+        self.subtask_progress(id, &mut vec![id])
+    }
+
+    fn subtask_progress(&self, id: u64, seen: &mut Vec<u64>) -> Option<u64> {
+        let subtasks = self.get_subtasks(id);
+        if subtasks.is_empty() {
+            return None;
+        }
+        let mut total = 0;
+        for subtask in &subtasks {
+            let finished = subtask.is_archived() || self.is_done_category(subtask.category);
+            total += match finished {
+                true => 100,
+                // Guards against cycles a merge could have made
+                false if seen.contains(&subtask.id) => 0,
+                false => {
+                    seen.push(subtask.id);
+                    self.subtask_progress(subtask.id, seen).unwrap_or(0)
+                }
+            };
+        }
+        Some(total / subtasks.len() as u64)
+    }
+
+    /// Turns a checklist item into a subtask of its task, returns the id of the subtask.
+    ///
+    /// A checked item becomes a subtask in the first done category.
+    pub fn promote_checklist_item(&mut self, task_id: u64, index: u64) -> Result<u64, PiqoError> {
+        let task = self.existing_task(task_id)?;
+        let item = task.check_list[checklist_position(task, index)?].clone();
+        let done_category = self
+            .categories
+            .iter()
+            .map(|c| c.id)
+            .find(|c| self.is_done_category(*c));
+
+        let id = self.add_task(item.name);
+        self.set_parent(id, Some(task_id))?;
+        if let Some(done_category) = done_category.filter(|_| item.checked) {
+            self.move_task(id, done_category)?;
+        }
+        self.remove_checklist_item(task_id, index)?;
+        Ok(id)
     }
 
-    pub(crate) fn clear_task_due_date(&mut self, id: u64) {
+    /// Tasks blocking the task, finished or not.
+    pub fn get_blockers(&self, id: u64) -> Vec<&Task> {
         self.tasks
-            .iter_mut()
-            .find(|t| t.id == id)
-            .unwrap()
-            .due_date_utc = None;
+            .iter()
+            .filter(|t| t.blocks.contains(&id))
+            .collect()
+    }
+
+    /// A task is blocked while any task blocking it is neither archived nor done.
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        self.get_blockers(task.id)
+            .iter()
+            .any(|b| !b.is_archived() && !self.is_done_category(b.category))
+    }
+
+    /// Whether tasks in the category count as finished, going by its name.
+    pub fn is_done_category(&self, category_id: u64) -> bool {
+        self.get_category_name(category_id).is_some_and(|name| {
+            DONE_CATEGORY_NAMES
+                .iter()
+                .any(|done| name.eq_ignore_ascii_case(done))
+        })
     }
 
-    pub(crate) fn get_task_due_time(&self, id: u64) -> Option<i64> {
-        self.tasks.iter().find(|t| t.id == id).unwrap().due_date_utc
+    pub fn get_task_due_time(&self, id: u64) -> Result<Option<i64>, PiqoError> {
+        Ok(self.existing_task(id)?.due_date_utc)
     }
+}
+
+/// Category names that mark a task as finished.
+const DONE_CATEGORY_NAMES: [&str; 6] = [
+    "done",
+    "closed",
+    "complete",
+    "completed",
+    "finished",
+    "resolved",
+];
+
+/// Category names that mark a task as being worked on.
+const IN_PROGRESS_CATEGORY_NAMES: [&str; 4] = ["in progress", "in-progress", "doing", "wip"];
+
+/// Position of the checklist item with the index in the task's checklist.
+fn checklist_position(task: &Task, check_list_index: u64) -> Result<usize, PiqoError> {
+    task.check_list
+        .iter()
+        .position(|c| c.index == check_list_index)
+        .ok_or_else(|| {
+            PiqoError::InvalidInput(format!(
+                "task {} has no checklist item {}",
+                task.id, check_list_index
+            ))
+        })
+}
 
+/// Serializes a value for the history, `None` for values that are unset.
+fn to_json<V: Serialize + ?Sized>(value: &V) -> Option<Value> {
+    serde_json::to_value(value).ok().filter(|v| !v.is_null())
 }
@@ -0,0 +1,741 @@
+//! Filter expressions for picking and ordering tasks, as used by `piqo list`.
+//!
+//! An expression is a list of terms separated by whitespace, a task has to match all of
+//! them. A term prefixed with `-` has to not match. Values with spaces can be quoted.
+//!
+//! ```text
+//! category:"In Progress" assignee:me due<7d -archived
+//! ```
+//!
+//! - `category:<name or id>`
+//! - `label:<name, id or none>`
+//! - `assignee:<me, none, email, name or id>`
+//! - `name:<text>`, matches part of the name; a bare word matches name or description
+//! - `id:<id>`
+//! - `parent:<id or none>`, subtasks of the task or top level tasks
+//! - `due`, `created` and `updated` compared with `<`, `<=`, `>`, `>=` or `:` against a
+//!   date like `2026-11-01` or a duration like `12h`, `7d` or `2w`. For `due` a duration
+//!   counts from now into the future, `due<7d` is due within a week. For `created` and
+//!   `updated` it is the age, `updated>2w` was last changed more than two weeks ago.
+//!   `due:none` matches tasks without a due date.
+//! - `progress` compared against the percentage of checked checklist items, tasks without
+//!   a checklist never match
+//! - `priority` compared by urgency with `<`, `<=`, `>`, `>=` or `:` against `P0` to `P3`,
+//!   `priority<P2` matches P0 and P1. `priority:none` matches tasks without a priority.
+//! - `archived`, `overdue`, `assigned` and `blocked` flags
+
+use std::cmp::Ordering;
+
+use chrono::{Local, NaiveDate, TimeZone};
+
+use crate::error::PiqoError;
+use crate::models::{Priority, Project, Task};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// A parsed filter expression together with how to order and cut the matching tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    terms: Vec<Term>,
+    archived: ArchivedTasks,
+    sort: Vec<SortKey>,
+    limit: Option<usize>,
+}
+
+/// Whether a query returns archived tasks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchivedTasks {
+    /// Archived tasks are left out, unless the expression asks for them with `archived`.
+    #[default]
+    Hidden,
+    Included,
+    Only,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    negated: bool,
+    condition: Condition,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Text(String),
+    Name(String),
+    Id(u64),
+    /// Subtasks of the task, top level tasks for `None`.
+    Parent(Option<u64>),
+    Category(String),
+    /// Tasks with the label, tasks without labels for `None`.
+    Label(Option<String>),
+    Assignee(String),
+    Archived,
+    Overdue,
+    Assigned,
+    Blocked,
+    NoDueDate,
+    Time(TimeField, Cmp, TimeOperand),
+    Progress(Cmp, u64),
+    /// Compared by urgency, `priority<P2` matches P0 and P1.
+    Priority(Cmp, Priority),
+    NoPriority,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TimeField {
+    Due,
+    Created,
+    Updated,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TimeOperand {
+    /// Seconds from now, into the future for due dates and into the past otherwise.
+    Duration(i64),
+    /// Start of a local day.
+    Day(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SortField {
+    Id,
+    Name,
+    Category,
+    Due,
+    Created,
+    Updated,
+    Progress,
+    Priority,
+}
+
+impl Query {
+    /// Parses a filter expression, an empty one matches every task.
+    pub fn parse(expression: &str) -> Result<Query, PiqoError> {
+        let terms = tokenize(expression)?
+            .iter()
+            .map(|token| parse_term(token))
+            .collect::<Result<Vec<Term>, PiqoError>>()?;
+        Ok(Query {
+            terms,
+            ..Query::default()
+        })
+    }
+
+    pub fn with_archived(mut self, archived: ArchivedTasks) -> Query {
+        self.archived = archived;
+        self
+    }
+
+    /// Orders the tasks by a comma separated list of fields, `-` sorts a field descending.
+    ///
+    /// Fields are `id`, `name`, `category`, `due`, `created`, `updated`, `progress` and
+    /// `priority`.
+    /// Tasks missing a value, like a due date, always come last.
+    pub fn sorted_by(mut self, sort: &str) -> Result<Query, PiqoError> {
+        self.sort = sort
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(parse_sort_key)
+            .collect::<Result<Vec<SortKey>, PiqoError>>()?;
+        Ok(self)
+    }
+
+    pub fn limited_to(mut self, limit: Option<usize>) -> Query {
+        self.limit = limit;
+        self
+    }
+
+    /// Adds the terms of another expression, tasks have to match both.
+    pub fn and(mut self, other: Query) -> Query {
+        self.terms.extend(other.terms);
+        self
+    }
+}
+
+impl Project {
+    /// Returns the tasks matching the query, sorted and limited as it asks for.
+    pub fn query(&self, query: &Query) -> Result<Vec<&Task>, PiqoError> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tasks = Vec::new();
+        for task in &self.tasks {
+            if self.matches_all(query, task, now)? {
+                tasks.push(task);
+            }
+        }
+
+        tasks.sort_by(|a, b| {
+            query
+                .sort
+                .iter()
+                .map(|key| self.compare(key, a, b))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        tasks.truncate(query.limit.unwrap_or(usize::MAX));
+        Ok(tasks)
+    }
+
+    fn matches_all(&self, query: &Query, task: &Task, now: i64) -> Result<bool, PiqoError> {
+        let asks_for_archived = query
+            .terms
+            .iter()
+            .any(|t| matches!(t.condition, Condition::Archived));
+        let visible = match query.archived {
+            ArchivedTasks::Hidden => asks_for_archived || !task.is_archived(),
+            ArchivedTasks::Included => true,
+            ArchivedTasks::Only => task.is_archived(),
+        };
+        if !visible {
+            return Ok(false);
+        }
+        for term in &query.terms {
+            if self.matches(&term.condition, task, now)? == term.negated {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn matches(&self, condition: &Condition, task: &Task, now: i64) -> Result<bool, PiqoError> {
+        let matched = match condition {
+            Condition::Text(text) => {
+                contains(&task.name, text) || contains(&task.description, text)
+            }
+            Condition::Name(text) => contains(&task.name, text),
+            Condition::Id(id) => task.id == *id,
+            Condition::Parent(parent) => task.parent_id == *parent,
+            Condition::Category(category) => task.category == self.resolve_category(category)?,
+            Condition::Label(Some(label)) => task.labels.contains(&self.resolve_label(label)?),
+            Condition::Label(None) => task.labels.is_empty(),
+            Condition::Assignee(user) => match self.query_assignee(user)? {
+                Some(id) => task.assigned_to.contains(&id),
+                None => task.assigned_to.is_empty(),
+            },
+            Condition::Archived => task.is_archived(),
+            Condition::Overdue => task.due_date_utc.is_some_and(|due| due < now),
+            Condition::Assigned => !task.assigned_to.is_empty(),
+            Condition::Blocked => self.is_blocked(task),
+            Condition::NoDueDate => task.due_date_utc.is_none(),
+            Condition::Time(field, cmp, operand) => {
+                let time = match field {
+                    TimeField::Due => task.due_date_utc,
+                    TimeField::Created => Some(task.created_at_utc),
+                    TimeField::Updated => Some(task.updated_at_utc),
+                };
+                match (time, operand) {
+                    (None, _) => false,
+                    (Some(due), TimeOperand::Duration(d)) if matches!(field, TimeField::Due) => {
+                        cmp.holds(due, now.saturating_add(*d))
+                    }
+                    (Some(time), TimeOperand::Duration(d)) => cmp.holds(now - time, *d),
+                    (Some(time), TimeOperand::Day(start)) => match cmp {
+                        Cmp::Eq => (*start..start + DAY).contains(&time),
+                        Cmp::Le | Cmp::Gt => cmp.holds(time, start + DAY - 1),
+                        Cmp::Lt | Cmp::Ge => cmp.holds(time, *start),
+                    },
+                }
+            }
+            Condition::Priority(cmp, priority) => {
+                task.priority.is_some_and(|p| cmp.holds(p, *priority))
+            }
+            Condition::NoPriority => task.priority.is_none(),
+            Condition::Progress(cmp, percent) => {
+                progress(task).is_some_and(|progress| cmp.holds(progress, *percent))
+            }
+        };
+        Ok(matched)
+    }
+
+    /// Resolves the user of an `assignee:` term, `None` stands for unassigned.
+    fn query_assignee(&self, user: &str) -> Result<Option<u64>, PiqoError> {
+        if user.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        if !user.eq_ignore_ascii_case("me") {
+            return self.resolve_user(user).map(Some);
+        }
+        match self.actor.as_deref() {
+            Some(actor) => self
+                .resolve_user(actor)
+                .map(Some)
+                .map_err(|_| PiqoError::UserNotFound(format!("me ({})", actor))),
+            None => Err(PiqoError::UserNotFound(
+                "me (no git email configured)".to_string(),
+            )),
+        }
+    }
+
+    fn compare(&self, key: &SortKey, a: &Task, b: &Task) -> Ordering {
+        let category_position = |t: &Task| self.categories.iter().position(|c| c.id == t.category);
+        let ordering = match key.field {
+            SortField::Id => compare_present(Some(a.id), Some(b.id)),
+            SortField::Name => {
+                compare_present(Some(a.name.to_lowercase()), Some(b.name.to_lowercase()))
+            }
+            SortField::Category => compare_present(category_position(a), category_position(b)),
+            SortField::Due => compare_present(a.due_date_utc, b.due_date_utc),
+            SortField::Created => compare_present(Some(a.created_at_utc), Some(b.created_at_utc)),
+            SortField::Updated => compare_present(Some(a.updated_at_utc), Some(b.updated_at_utc)),
+            SortField::Progress => compare_present(progress(a), progress(b)),
+            SortField::Priority => compare_present(a.priority, b.priority),
+        };
+        match ordering {
+            Present::Both(o) if key.descending => o.reverse(),
+            Present::Both(o) => o,
+            Present::Missing(o) => o,
+        }
+    }
+}
+
+/// Result of comparing values that may be missing, missing values sort last either way.
+enum Present {
+    Both(Ordering),
+    Missing(Ordering),
+}
+
+fn compare_present<T: Ord>(a: Option<T>, b: Option<T>) -> Present {
+    match (a, b) {
+        (Some(a), Some(b)) => Present::Both(a.cmp(&b)),
+        (Some(_), None) => Present::Missing(Ordering::Less),
+        (None, Some(_)) => Present::Missing(Ordering::Greater),
+        (None, None) => Present::Missing(Ordering::Equal),
+    }
+}
+
+impl Cmp {
+    fn holds<T: Ord>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+            Cmp::Eq => a == b,
+        }
+    }
+}
+
+/// Percentage of checked checklist items, `None` without a checklist.
+fn progress(task: &Task) -> Option<u64> {
+    let (checked, total) = task.checklist_progress()?;
+    Some((checked * 100 / total) as u64)
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// Splits on whitespace outside of double quotes and drops the quotes.
+fn tokenize(expression: &str) -> Result<Vec<String>, PiqoError> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in expression.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid(expression, "unterminated quote"));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_term(token: &str) -> Result<Term, PiqoError> {
+    let (negated, term) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    let Some(split) = term.find([':', '<', '>', '=']) else {
+        let condition = match term.to_lowercase().as_str() {
+            "archived" => Condition::Archived,
+            "overdue" => Condition::Overdue,
+            "assigned" => Condition::Assigned,
+            "blocked" => Condition::Blocked,
+            _ => Condition::Text(term.to_string()),
+        };
+        return Ok(Term { negated, condition });
+    };
+
+    let field = term[..split].to_lowercase();
+    let rest = &term[split..];
+    let (cmp, value) = [
+        ("<=", Cmp::Le),
+        (">=", Cmp::Ge),
+        ("<", Cmp::Lt),
+        (">", Cmp::Gt),
+        (":", Cmp::Eq),
+        ("=", Cmp::Eq),
+    ]
+    .into_iter()
+    .find_map(|(op, cmp)| rest.strip_prefix(op).map(|value| (cmp, value)))
+    .unwrap();
+    if value.is_empty() {
+        return Err(invalid(token, "missing value"));
+    }
+
+    let equality = |condition: Condition| match cmp {
+        Cmp::Eq => Ok(condition),
+        _ => Err(invalid(token, "only : is supported for this field")),
+    };
+    let condition = match field.as_str() {
+        "name" => equality(Condition::Name(value.to_string()))?,
+        "id" => equality(Condition::Id(
+            value.parse().map_err(|_| invalid(token, "invalid id"))?,
+        ))?,
+        "parent" if value.eq_ignore_ascii_case("none") => equality(Condition::Parent(None))?,
+        "parent" => equality(Condition::Parent(Some(
+            value.parse().map_err(|_| invalid(token, "invalid id"))?,
+        )))?,
+        "category" => equality(Condition::Category(value.to_string()))?,
+        "label" if value.eq_ignore_ascii_case("none") => equality(Condition::Label(None))?,
+        "label" => equality(Condition::Label(Some(value.to_string())))?,
+        "assignee" | "assigned" => equality(Condition::Assignee(value.to_string()))?,
+        "due" if value.eq_ignore_ascii_case("none") => equality(Condition::NoDueDate)?,
+        "due" => Condition::Time(TimeField::Due, cmp, parse_time(token, cmp, value)?),
+        "created" => Condition::Time(TimeField::Created, cmp, parse_time(token, cmp, value)?),
+        "updated" => Condition::Time(TimeField::Updated, cmp, parse_time(token, cmp, value)?),
+        "priority" if value.eq_ignore_ascii_case("none") => equality(Condition::NoPriority)?,
+        "priority" => Condition::Priority(
+            cmp,
+            value
+                .parse()
+                .map_err(|_| invalid(token, "expected P0, P1, P2 or P3"))?,
+        ),
+        "progress" => Condition::Progress(
+            cmp,
+            value
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| invalid(token, "expected a percentage"))?,
+        ),
+        _ => return Err(invalid(token, "unknown field")),
+    };
+    Ok(Term { negated, condition })
+}
+
+fn parse_time(token: &str, cmp: Cmp, value: &str) -> Result<TimeOperand, PiqoError> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let start = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .ok_or_else(|| invalid(token, "invalid local date"))?;
+        return Ok(TimeOperand::Day(start.timestamp()));
+    }
+
+    if matches!(cmp, Cmp::Eq) {
+        return Err(invalid(token, "compare durations with < or >"));
+    }
+    let duration = parse_duration(value)
+        .map_err(|_| invalid(token, "expected a date or a duration like 12h, 7d or 2w"))?;
+    Ok(TimeOperand::Duration(duration))
+}
+
+/// Parses a positive duration like `12h`, `7d` or `2w` into seconds.
+pub fn parse_duration(duration: &str) -> Result<i64, PiqoError> {
+    let error = || {
+        PiqoError::InvalidInput(format!(
+            "invalid duration {}, expected e.g. 12h, 7d or 2w",
+            duration
+        ))
+    };
+    let unit = match duration.chars().last() {
+        Some('h') => 60 * 60,
+        Some('d') => DAY,
+        Some('w') => 7 * DAY,
+        _ => return Err(error()),
+    };
+    let amount = &duration[..duration.len() - 1];
+    if amount.is_empty() || !amount.chars().all(|c| c.is_ascii_digit()) {
+        return Err(error());
+    }
+    match amount.parse::<i64>() {
+        Ok(amount) if amount > 0 => amount.checked_mul(unit).ok_or_else(error),
+        _ => Err(error()),
+    }
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, PiqoError> {
+    let (descending, name) = match key.strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, key),
+    };
+    let field = match name.to_lowercase().as_str() {
+        "id" => SortField::Id,
+        "name" => SortField::Name,
+        "category" => SortField::Category,
+        "due" => SortField::Due,
+        "created" => SortField::Created,
+        "updated" => SortField::Updated,
+        "progress" => SortField::Progress,
+        "priority" => SortField::Priority,
+        _ => {
+            return Err(PiqoError::InvalidInput(format!(
+                "unknown sort field {}, expected one of id, name, category, due, created, updated, progress, priority",
+                name
+            )));
+        }
+    };
+    Ok(SortKey { field, descending })
+}
+
+fn invalid(term: &str, reason: &str) -> PiqoError {
+    PiqoError::InvalidInput(format!("invalid filter {}: {}", term, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A project with tasks `first` to `fourth`, in that order, returning their ids.
+    fn project() -> (Project, [u64; 4]) {
+        let mut p = Project::new("test".to_string());
+        p.add_default_category("Todo");
+        p.add_category("In Progress");
+        p.add_user("Bob", "Bob@x.com");
+        p.add_user("Alice", "alice@x.com");
+        p.set_actor(Some("alice@x.com".to_string()));
+        let ids = ["first", "second", "third", "fourth"].map(|name| p.add_task(name.to_string()));
+        (p, ids)
+    }
+
+    fn user(p: &Project, name: &str) -> u64 {
+        p.resolve_user(name).unwrap()
+    }
+
+    fn matching(p: &Project, expression: &str) -> Vec<u64> {
+        p.query(&Query::parse(expression).unwrap())
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    }
+
+    fn sorted(p: &Project, sort: &str) -> Vec<u64> {
+        let query = Query::default().sorted_by(sort).unwrap();
+        p.query(&query).unwrap().iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn tokenizes_quoted_values() {
+        assert_eq!(
+            tokenize(r#"category:"In Progress"  due<7d"#).unwrap(),
+            vec!["category:In Progress", "due<7d"]
+        );
+        assert!(tokenize(r#"name:"open"#).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_terms() {
+        for term in [
+            "unknown:x",
+            "name<x",
+            "category:",
+            "id:abc",
+            "due:7d",
+            "due<soon",
+            "priority<P9",
+            "progress>half",
+        ] {
+            assert!(Query::parse(term).is_err(), "{}", term);
+        }
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("12h").unwrap(), 12 * 60 * 60);
+        assert_eq!(parse_duration("7d").unwrap(), 7 * DAY);
+        assert_eq!(parse_duration("2w").unwrap(), 14 * DAY);
+    }
+
+    #[test]
+    fn rejects_durations_that_are_not_positive_or_overflow() {
+        for duration in [
+            "",
+            "d",
+            "0h",
+            "-1d",
+            "+5d",
+            "1.5d",
+            "5x",
+            "99999999999999999w",
+        ] {
+            assert!(parse_duration(duration).is_err(), "{}", duration);
+        }
+        assert!(Query::parse("created<99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn matches_text_names_and_ids() {
+        let (mut p, [first, second, ..]) = project();
+        p.edit_task_description(second, "the FIRST follow up".to_string())
+            .unwrap();
+
+        assert_eq!(matching(&p, "first"), vec![first, second]);
+        assert_eq!(matching(&p, "name:first"), vec![first]);
+        assert_eq!(matching(&p, &format!("id:{}", second)), vec![second]);
+        assert_eq!(matching(&p, "first -name:first"), vec![second]);
+    }
+
+    #[test]
+    fn matches_categories_by_name_or_id() {
+        let (mut p, [first, second, third, fourth]) = project();
+        let in_progress = p.resolve_category("in progress").unwrap();
+        p.move_task(second, in_progress).unwrap();
+
+        assert_eq!(matching(&p, r#"category:"IN PROGRESS""#), vec![second]);
+        assert_eq!(
+            matching(&p, &format!("category:{}", in_progress)),
+            vec![second]
+        );
+        assert_eq!(matching(&p, "-category:todo"), vec![second]);
+        assert_eq!(matching(&p, "category:todo"), vec![first, third, fourth]);
+        assert!(p.query(&Query::parse("category:nope").unwrap()).is_err());
+    }
+
+    #[test]
+    fn matches_assignees_like_other_commands_resolve_users() {
+        let (mut p, [first, second, third, fourth]) = project();
+        p.assign_task(user(&p, "bob"), first).unwrap();
+        p.assign_task(user(&p, "alice"), second).unwrap();
+
+        assert_eq!(matching(&p, "assignee:bob@X.com"), vec![first]);
+        assert_eq!(matching(&p, "assignee:BOB"), vec![first]);
+        assert_eq!(matching(&p, "assignee:me"), vec![second]);
+        assert_eq!(matching(&p, "assignee:none"), vec![third, fourth]);
+        assert_eq!(matching(&p, "assigned"), vec![first, second]);
+
+        p.set_actor(None);
+        assert!(p.query(&Query::parse("assignee:me").unwrap()).is_err());
+    }
+
+    #[test]
+    fn matches_labels() {
+        let (mut p, [first, second, third, fourth]) = project();
+        let bug = p.add_label("bug", None).unwrap();
+        p.label_task(first, bug).unwrap();
+
+        assert_eq!(matching(&p, "label:BUG"), vec![first]);
+        assert_eq!(matching(&p, &format!("label:{}", bug)), vec![first]);
+        assert_eq!(matching(&p, "label:none"), vec![second, third, fourth]);
+    }
+
+    #[test]
+    fn hides_archived_tasks_unless_asked_for() {
+        let (mut p, [first, second, third, fourth]) = project();
+        p.archive_task(first).unwrap();
+
+        assert_eq!(matching(&p, ""), vec![second, third, fourth]);
+        assert_eq!(matching(&p, "archived"), vec![first]);
+        let included = Query::default().with_archived(ArchivedTasks::Included);
+        assert_eq!(p.query(&included).unwrap().len(), 4);
+        let only = Query::default().with_archived(ArchivedTasks::Only);
+        assert_eq!(p.query(&only).unwrap()[0].id, first);
+    }
+
+    #[test]
+    fn compares_due_dates() {
+        let (mut p, [first, second, third, _]) = project();
+        let now = chrono::Utc::now().timestamp();
+        p.set_task_due_date(first, now - DAY).unwrap();
+        p.set_task_due_date(second, now + 3 * DAY).unwrap();
+        p.set_task_due_date(third, now + 30 * DAY).unwrap();
+
+        assert_eq!(matching(&p, "overdue"), vec![first]);
+        assert_eq!(matching(&p, "due<7d"), vec![first, second]);
+        assert_eq!(matching(&p, "due>7d"), vec![third]);
+        assert_eq!(matching(&p, "due:none").len(), 1);
+        assert_eq!(
+            matching(&p, "due<9999999999999w"),
+            vec![first, second, third]
+        );
+    }
+
+    #[test]
+    fn compares_ages() {
+        let (mut p, [first, ..]) = project();
+        p.tasks[0].created_at_utc -= 30 * DAY;
+
+        assert_eq!(matching(&p, "created>2w"), vec![first]);
+        assert_eq!(matching(&p, "created<2w").len(), 3);
+    }
+
+    #[test]
+    fn compares_priority_and_progress() {
+        let (mut p, [first, second, third, _]) = project();
+        p.set_task_priority(first, Priority::P0).unwrap();
+        p.set_task_priority(second, Priority::P2).unwrap();
+        for name in ["a", "b"] {
+            p.add_checklist_item(third, name.to_string()).unwrap();
+        }
+        let index = p.get_task(third).unwrap().check_list[0].index;
+        p.set_checklist_item_checked(third, index, true).unwrap();
+
+        assert_eq!(matching(&p, "priority<P2"), vec![first]);
+        assert_eq!(matching(&p, "priority<=p2"), vec![first, second]);
+        assert_eq!(matching(&p, "priority:none").len(), 2);
+        assert_eq!(matching(&p, "progress>=50%"), vec![third]);
+        assert!(matching(&p, "progress<50").is_empty());
+    }
+
+    #[test]
+    fn sorts_by_keys_with_missing_values_last() {
+        let (mut p, [first, second, third, fourth]) = project();
+        p.set_task_due_date(second, 200).unwrap();
+        p.set_task_due_date(third, 100).unwrap();
+        p.set_task_priority(first, Priority::P1).unwrap();
+        p.set_task_priority(fourth, Priority::P1).unwrap();
+        p.set_task_priority(third, Priority::P0).unwrap();
+
+        assert_eq!(sorted(&p, "due"), vec![third, second, first, fourth]);
+        assert_eq!(sorted(&p, "-due"), vec![second, third, first, fourth]);
+        assert_eq!(sorted(&p, "name"), vec![first, fourth, second, third]);
+        assert_eq!(
+            sorted(&p, "priority, -name"),
+            vec![third, fourth, first, second]
+        );
+        assert!(Query::default().sorted_by("size").is_err());
+    }
+
+    #[test]
+    fn limits_after_sorting() {
+        let (p, [first, _, _, fourth]) = project();
+        let query = Query::default()
+            .sorted_by("name")
+            .unwrap()
+            .limited_to(Some(2));
+
+        let ids = p
+            .query(&query)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<u64>>();
+        assert_eq!(ids, vec![first, fourth]);
+    }
+}
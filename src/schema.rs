@@ -0,0 +1,191 @@
+use serde_json::Value;
+
+use crate::error::PiqoError;
+
+/// Version of the stored project this piqo writes, bump it together with a new migration.
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Upgrades a stored project from `from` to `from + 1`.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    apply: fn(&mut Value),
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "add schema version and fill in task fields missing from old files",
+        apply: fill_task_fields,
+    },
+    Migration {
+        from: 1,
+        description: "add an empty change history",
+        apply: add_history,
+    },
+    Migration {
+        from: 2,
+        description: "add task dependencies",
+        apply: add_task_blocks,
+    },
+    Migration {
+        from: 3,
+        description: "add subtasks",
+        apply: add_task_parents,
+    },
+    Migration {
+        from: 4,
+        description: "add task priorities",
+        apply: add_task_priorities,
+    },
+    Migration {
+        from: 5,
+        description: "add labels",
+        apply: add_labels,
+    },
+    Migration {
+        from: 6,
+        description: "add estimates and time tracking",
+        apply: add_time_tracking,
+    },
+    Migration {
+        from: 7,
+        description: "add recurring tasks",
+        apply: add_task_recurrence,
+    },
+    Migration {
+        from: 8,
+        description: "add the category commits move fixed tasks to",
+        apply: add_commit_fixes_category,
+    },
+];
+
+/// Files written before versioning have no `schema_version` and count as version 0.
+pub fn version_of(project: &Value) -> u32 {
+    project
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32
+}
+
+/// Returns the migrations `migrate` would run on the project.
+pub fn pending_migrations(project: &Value) -> Result<Vec<&'static Migration>, PiqoError> {
+    let version = version_of(project);
+    if version > SCHEMA_VERSION {
+        return Err(PiqoError::UnsupportedSchemaVersion(version));
+    }
+    Ok(MIGRATIONS.iter().filter(|m| m.from >= version).collect())
+}
+
+/// Upgrades the project step by step to `SCHEMA_VERSION`.
+pub fn migrate(project: &mut Value) -> Result<(), PiqoError> {
+    for migration in pending_migrations(project)? {
+        (migration.apply)(project);
+        project["schema_version"] = Value::from(migration.from + 1);
+    }
+    Ok(())
+}
+
+fn fill_task_fields(project: &mut Value) {
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        let created_at = task
+            .get("created_at_utc")
+            .cloned()
+            .unwrap_or(Value::from(0));
+        task.entry("description").or_insert(Value::from(""));
+        task.entry("updated_at_utc").or_insert(created_at);
+        task.entry("archived_at_utc").or_insert(Value::Null);
+        task.entry("due_date_utc").or_insert(Value::Null);
+        task.entry("assigned_to").or_insert(Value::Array(vec![]));
+        task.entry("check_list").or_insert(Value::Array(vec![]));
+
+        let last_index = task["check_list"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.get("index").and_then(|i| i.as_u64()))
+            .max()
+            .unwrap_or(0);
+        task.entry("last_check_list_index")
+            .or_insert(Value::from(last_index));
+    }
+}
+
+fn add_history(project: &mut Value) {
+    if let Some(project) = project.as_object_mut() {
+        project.entry("history").or_insert(Value::Array(vec![]));
+    }
+}
+
+fn add_task_blocks(project: &mut Value) {
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("blocks").or_insert(Value::Array(vec![]));
+    }
+}
+
+fn add_task_parents(project: &mut Value) {
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("parent_id").or_insert(Value::Null);
+    }
+}
+
+fn add_task_priorities(project: &mut Value) {
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("priority").or_insert(Value::Null);
+    }
+}
+
+fn add_labels(project: &mut Value) {
+    if let Some(project) = project.as_object_mut() {
+        project.entry("labels").or_insert(Value::Array(vec![]));
+    }
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("labels").or_insert(Value::Array(vec![]));
+    }
+}
+
+fn add_time_tracking(project: &mut Value) {
+    if let Some(project) = project.as_object_mut() {
+        project
+            .entry("time_entries")
+            .or_insert(Value::Array(vec![]));
+    }
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("estimate").or_insert(Value::Null);
+    }
+}
+
+fn add_task_recurrence(project: &mut Value) {
+    let Some(tasks) = project.get_mut("tasks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for task in tasks.iter_mut().filter_map(|t| t.as_object_mut()) {
+        task.entry("recurrence").or_insert(Value::Null);
+    }
+}
+
+fn add_commit_fixes_category(project: &mut Value) {
+    if let Some(project) = project.as_object_mut() {
+        project
+            .entry("commit_fixes_category")
+            .or_insert(Value::Null);
+    }
+}
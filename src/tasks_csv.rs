@@ -0,0 +1,464 @@
+//! Tasks as CSV for editing them in spreadsheets.
+//!
+//! The columns are [`COLUMNS`]. Assignees are emails, or names for users without one,
+//! separated by commas. The checklist has one item per line, `[x] name` for checked items
+//! and `[ ] name` for the rest.
+
+use std::io::{Read, Write};
+
+use chrono::{DateTime, SecondsFormat};
+
+use crate::error::PiqoError;
+use crate::models::{Project, Task, TaskJson};
+use crate::utils;
+
+/// Columns of exported tasks, imports match columns by these names in any order.
+pub const COLUMNS: [&str; 8] = [
+    "id",
+    "name",
+    "description",
+    "category",
+    "assignees",
+    "due_date",
+    "checklist",
+    "archived",
+];
+
+/// What importing a row did, or would do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedRow {
+    Created {
+        line: u64,
+        task_id: u64,
+    },
+    /// The task with the names of the columns that changed.
+    Updated {
+        line: u64,
+        task_id: u64,
+        columns: Vec<&'static str>,
+    },
+    Unchanged {
+        line: u64,
+        task_id: u64,
+    },
+}
+
+/// Writes the tasks with a header row.
+pub fn export(p: &Project, tasks: &[&Task], writer: impl Write) -> Result<(), PiqoError> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(COLUMNS).map_err(csv_error)?;
+    for task in tasks {
+        csv.write_record(row(&p.task_json(task)))
+            .map_err(csv_error)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn row(task: &TaskJson) -> [String; 8] {
+    let assignees = task
+        .assigned_to
+        .iter()
+        .map(|u| u.git_email.clone().unwrap_or_else(|| u.name.clone()))
+        .collect::<Vec<String>>();
+    let due_date = match task.due_date_utc_unix {
+        0 => String::new(),
+        due => DateTime::from_timestamp(due, 0)
+            .unwrap()
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let checklist = task
+        .check_list
+        .iter()
+        .map(|c| format!("[{}] {}", if c.checked { "x" } else { " " }, c.name))
+        .collect::<Vec<String>>();
+    [
+        task.id.to_string(),
+        task.name.clone(),
+        task.description.clone(),
+        task.category.clone(),
+        assignees.join(", "),
+        due_date,
+        checklist.join("\n"),
+        (task.archived_at_utc_unix != 0).to_string(),
+    ]
+}
+
+/// Creates a task for each row without the id of an existing task and updates the others.
+///
+/// Only the columns present are imported, so a file without a `checklist` column leaves
+/// checklists alone. An empty `category` means the default category. Unknown categories
+/// and users are created when `create_missing` is set, users only if given by email,
+/// otherwise they fail the import. The project is changed row by row, on errors it is
+/// left partially imported and shouldn't be stored.
+///
+/// With `dry_run` finished recurring tasks don't come back, the project is only imported
+/// into to report what would change.
+pub fn import(
+    p: &mut Project,
+    reader: impl Read,
+    create_missing: bool,
+    dry_run: bool,
+) -> Result<Vec<ImportedRow>, PiqoError> {
+    let mut csv = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = csv.headers().map_err(csv_error)?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let Some(name_column) = column("name") else {
+        return Err(PiqoError::InvalidInput(
+            "the csv has no name column".to_string(),
+        ));
+    };
+    let columns = COLUMNS.map(column);
+
+    let mut imported = Vec::new();
+    for record in csv.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, |pos| pos.line());
+        let cell = |index: usize| columns[index].map(|c| record.get(c).unwrap_or(""));
+        let row_error = |msg: String| PiqoError::InvalidInput(format!("line {}: {}", line, msg));
+        let row_cause = |err: PiqoError| row_error(err.to_string());
+
+        let existing = match cell(0).filter(|id| !id.is_empty()) {
+            Some(id) => {
+                let id = id
+                    .parse::<u64>()
+                    .map_err(|_| row_error(format!("invalid id {}", id)))?;
+                p.get_task(id).map(|t| t.id)
+            }
+            None => None,
+        };
+        let name = record.get(name_column).unwrap_or("");
+        if name.is_empty() {
+            return Err(row_error("the name is empty".to_string()));
+        }
+        let (task_id, before) = match existing {
+            Some(id) => {
+                p.edit_task_name(id, name.to_string())?;
+                (id, p.get_task(id).cloned())
+            }
+            None => (p.add_task(name.to_string()), None),
+        };
+
+        if let Some(description) = cell(2) {
+            p.edit_task_description(task_id, description.to_string())?;
+        }
+        if let Some(category) = cell(3) {
+            let category = resolve_category(p, category, create_missing).map_err(row_cause)?;
+            p.move_task_inner(task_id, category, !dry_run)?;
+        }
+        if let Some(assignees) = cell(4) {
+            let users = assignees
+                .split(',')
+                .map(str::trim)
+                .filter(|u| !u.is_empty())
+                .map(|u| resolve_user(p, u, create_missing))
+                .collect::<Result<Vec<u64>, PiqoError>>()
+                .map_err(row_cause)?;
+            set_assignees(p, task_id, &users)?;
+        }
+        if let Some(due_date) = cell(5) {
+            match due_date {
+                "" => p.clear_task_due_date(task_id)?,
+                due_date => {
+                    let due_date = utils::parse_due_date(due_date).map_err(row_cause)?;
+                    p.set_task_due_date(task_id, due_date)?
+                }
+            }
+        }
+        if let Some(checklist) = cell(6) {
+            set_checklist(p, task_id, &parse_checklist(checklist))?;
+        }
+        if let Some(archived) = cell(7) {
+            match parse_bool(archived).map_err(row_error)? {
+                true => p.archive_task_inner(task_id, !dry_run).map(|_| ())?,
+                false => p.unarchive_task(task_id)?,
+            }
+        }
+
+        imported.push(match before {
+            None => ImportedRow::Created { line, task_id },
+            Some(before) => {
+                let columns = changed_columns(&before, p.get_task(task_id).unwrap());
+                if columns.is_empty() {
+                    ImportedRow::Unchanged { line, task_id }
+                } else {
+                    ImportedRow::Updated {
+                        line,
+                        task_id,
+                        columns,
+                    }
+                }
+            }
+        });
+    }
+    Ok(imported)
+}
+
+fn csv_error(err: csv::Error) -> PiqoError {
+    PiqoError::InvalidInput(format!("invalid csv: {}", err))
+}
+
+fn resolve_category(p: &mut Project, name: &str, create: bool) -> Result<u64, PiqoError> {
+    if name.is_empty() {
+        return Ok(p.default_category);
+    }
+    match p.resolve_category(name) {
+        Err(PiqoError::CategoryNotFound(_)) if create => {
+            p.add_category(name);
+            p.resolve_category(name)
+        }
+        resolved => resolved,
+    }
+}
+
+/// Like [`Project::resolve_user`], users given by email are created if `create` is set.
+fn resolve_user(p: &mut Project, user: &str, create: bool) -> Result<u64, PiqoError> {
+    match p.resolve_user(user) {
+        Err(PiqoError::UserNotFound(_)) if create && user.contains('@') => {
+            let name = user.split('@').next().unwrap_or(user);
+            p.add_user(name, user);
+            p.resolve_user(user)
+        }
+        resolved => resolved,
+    }
+}
+
+fn set_assignees(p: &mut Project, task_id: u64, users: &[u64]) -> Result<(), PiqoError> {
+    let assigned = p.get_task(task_id).unwrap().assigned_to.clone();
+    for user in assigned.iter().filter(|u| !users.contains(u)) {
+        p.unassign_task(*user, task_id)?;
+    }
+    for user in users {
+        p.assign_task(*user, task_id)?;
+    }
+    Ok(())
+}
+
+/// Parses checklist items, one per line, as (checked, name).
+fn parse_checklist(checklist: &str) -> Vec<(bool, String)> {
+    checklist
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| match l.get(..3) {
+            Some("[x]" | "[X]") => (true, l[3..].trim().to_string()),
+            Some("[ ]") => (false, l[3..].trim().to_string()),
+            _ => (false, l.to_string()),
+        })
+        .collect()
+}
+
+/// Makes the checklist match the items in order, keeping items with the same name.
+fn set_checklist(p: &mut Project, task_id: u64, items: &[(bool, String)]) -> Result<(), PiqoError> {
+    let mut unmatched = p.get_task_checklist(task_id)?;
+    let mut indexes = Vec::new();
+    for (checked, name) in items {
+        let index = match unmatched.iter().position(|c| &c.name == name) {
+            Some(position) => unmatched.remove(position).index,
+            None => {
+                p.add_checklist_item(task_id, name.clone())?;
+                p.get_task(task_id).unwrap().last_check_list_index
+            }
+        };
+        p.set_checklist_item_checked(task_id, index, *checked)?;
+        indexes.push(index);
+    }
+    for item in unmatched {
+        p.remove_checklist_item(task_id, item.index)?;
+    }
+    for (position, index) in indexes.iter().enumerate() {
+        p.move_checklist_item(task_id, *index, position + 1)?;
+    }
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "" | "false" | "no" | "0" => Ok(false),
+        "true" | "yes" | "1" | "x" => Ok(true),
+        _ => Err(format!("invalid archived value {}", value)),
+    }
+}
+
+fn changed_columns(before: &Task, after: &Task) -> Vec<&'static str> {
+    let mut sorted_assignees = [before.assigned_to.clone(), after.assigned_to.clone()];
+    sorted_assignees.iter_mut().for_each(|a| a.sort());
+    let changed = [
+        false,
+        before.name != after.name,
+        before.description != after.description,
+        before.category != after.category,
+        sorted_assignees[0] != sorted_assignees[1],
+        before.due_date_utc != after.due_date_utc,
+        before.check_list != after.check_list,
+        before.is_archived() != after.is_archived(),
+    ];
+    COLUMNS
+        .iter()
+        .zip(changed)
+        .filter(|(_, changed)| *changed)
+        .map(|(column, _)| *column)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Recurrence, RecurrenceUnit};
+
+    use super::*;
+
+    fn project() -> (Project, u64, u64) {
+        let mut p = Project::new("test".to_string());
+        p.add_default_category("Todo");
+        p.add_category("Done");
+        p.add_user("Bob", "bob@x.com");
+        p.add_user("Alice", "alice@x.com");
+
+        let first = p.add_task("Write, then \"ship\"".to_string());
+        p.edit_task_description(first, "line one\nline two".to_string())
+            .unwrap();
+        for user in ["bob@x.com", "alice@x.com"] {
+            p.assign_task(p.resolve_user(user).unwrap(), first).unwrap();
+        }
+        p.set_task_due_date(first, 1_800_000_000).unwrap();
+        for item in ["draft", "review"] {
+            p.add_checklist_item(first, item.to_string()).unwrap();
+        }
+        let index = p.get_task(first).unwrap().check_list[0].index;
+        p.set_checklist_item_checked(first, index, true).unwrap();
+
+        let second = p.add_task("Old".to_string());
+        p.move_task(second, p.resolve_category("done").unwrap())
+            .unwrap();
+        p.archive_task(second).unwrap();
+        (p, first, second)
+    }
+
+    fn exported(p: &Project) -> String {
+        let tasks = p.tasks.iter().collect::<Vec<&Task>>();
+        let mut out = Vec::new();
+        export(p, &tasks, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn exported_tasks_import_unchanged() {
+        let (mut p, first, second) = project();
+        let csv = exported(&p);
+        let before = serde_json::to_value(&p.tasks).unwrap();
+
+        let rows = import(&mut p, csv.as_bytes(), false, false).unwrap();
+
+        assert_eq!(
+            rows,
+            vec![
+                ImportedRow::Unchanged {
+                    line: 2,
+                    task_id: first
+                },
+                ImportedRow::Unchanged {
+                    line: 5,
+                    task_id: second
+                },
+            ]
+        );
+        assert_eq!(serde_json::to_value(&p.tasks).unwrap(), before);
+    }
+
+    #[test]
+    fn imports_changed_columns_in_any_order() {
+        let (mut p, first, _) = project();
+        let csv = format!(
+            "checklist,Category,name,id\n\"[ ] review\n[x] test\",done,{},{}\n",
+            "\"Write, then \"\"ship\"\"\"", first
+        );
+
+        let rows = import(&mut p, csv.as_bytes(), false, false).unwrap();
+
+        assert_eq!(
+            rows,
+            vec![ImportedRow::Updated {
+                line: 2,
+                task_id: first,
+                columns: vec!["category", "checklist"],
+            }]
+        );
+        let task = p.get_task(first).unwrap();
+        assert_eq!(task.category, p.resolve_category("Done").unwrap());
+        let checklist = task
+            .check_list
+            .iter()
+            .map(|c| (c.index, c.name.as_str(), c.checked))
+            .collect::<Vec<(u64, &str, bool)>>();
+        assert_eq!(checklist, vec![(2, "review", false), (3, "test", true)]);
+        assert_eq!(task.assigned_to.len(), 2);
+    }
+
+    #[test]
+    fn creates_rows_without_an_existing_id() {
+        let (mut p, _, _) = project();
+        let csv = "name,category,assignees,due_date\nNew,,BOB@x.com,\n";
+
+        let rows = import(&mut p, csv.as_bytes(), false, false).unwrap();
+
+        let [ImportedRow::Created { task_id, .. }] = rows[..] else {
+            panic!("expected a created row, got {:?}", rows);
+        };
+        let task = p.get_task(task_id).unwrap();
+        assert_eq!(task.name, "New");
+        assert_eq!(task.category, p.default_category);
+        assert_eq!(task.assigned_to, vec![p.resolve_user("bob@x.com").unwrap()]);
+    }
+
+    #[test]
+    fn fails_on_unknown_values_unless_creating_them() {
+        let (mut p, _, _) = project();
+        let csv = "name,category,assignees\nNew,Review,carol@x.com\n";
+
+        let err = import(&mut p, csv.as_bytes(), false, false).unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"), "{}", err);
+
+        let (mut p, _, _) = project();
+        import(&mut p, csv.as_bytes(), true, false).unwrap();
+        assert!(p.resolve_category("review").is_ok());
+        assert!(p.resolve_user("carol@x.com").is_ok());
+    }
+
+    #[test]
+    fn dry_runs_leave_recurring_tasks_alone() {
+        for dry_run in [true, false] {
+            let (mut p, first, _) = project();
+            let weekly = Recurrence {
+                every: 1,
+                unit: RecurrenceUnit::Week,
+            };
+            p.set_task_recurrence(first, weekly).unwrap();
+            let tasks = p.tasks.len();
+            let csv = format!("id,name,archived\n{},Weekly,true\n", first);
+
+            import(&mut p, csv.as_bytes(), false, dry_run).unwrap();
+
+            assert!(p.get_task(first).unwrap().is_archived());
+            let occurrences = if dry_run { 0 } else { 1 };
+            assert_eq!(p.tasks.len(), tasks + occurrences, "dry run {}", dry_run);
+        }
+    }
+
+    #[test]
+    fn dry_runs_moving_recurring_tasks_to_done_leave_them_alone() {
+        let (mut p, first, _) = project();
+        let daily = Recurrence {
+            every: 1,
+            unit: RecurrenceUnit::Day,
+        };
+        p.set_task_recurrence(first, daily).unwrap();
+        let tasks = p.tasks.len();
+        let csv = format!("id,name,category\n{},Daily,Done\n", first);
+
+        import(&mut p, csv.as_bytes(), false, true).unwrap();
+
+        assert_eq!(p.tasks.len(), tasks);
+    }
+}
@@ -1,6 +1,9 @@
-use owo_colors::{colors::xterm, Style};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use owo_colors::{Style, colors::xterm};
 use rand::Rng;
 
+use crate::error::PiqoError;
+
 fn extract_email(output: std::process::Output) -> Option<String> {
     if output.status.success() {
         let git_email = String::from_utf8(output.stdout).unwrap();
@@ -17,7 +20,7 @@ fn get_git_email(t: &str) -> std::process::Output {
         .expect("Failed to execute git command")
 }
 
-pub(crate) fn get_local_git_email() -> Option<String> {
+pub fn get_local_git_email() -> Option<String> {
     let output = get_git_email("local");
     let local_email = extract_email(output);
     if local_email.is_some() {
@@ -26,12 +29,12 @@ pub(crate) fn get_local_git_email() -> Option<String> {
     extract_email(get_git_email("global"))
 }
 
-pub(crate) fn gen_4digit_id() -> u64 {
+pub fn gen_4digit_id() -> u64 {
     let mut rng = rand::rng();
     rng.random_range(1000..9999)
 }
 
-pub(crate) fn get_unused_id(current_ids: Vec<u64>) -> u64 {
+pub fn get_unused_id(current_ids: Vec<u64>) -> u64 {
     let mut rand_id = gen_4digit_id();
 
     loop {
@@ -44,35 +47,89 @@ pub(crate) fn get_unused_id(current_ids: Vec<u64>) -> u64 {
     rand_id
 }
 
-pub(crate) fn truncate(s: &str, max_len: usize) -> String {
+/// Parses time spent like `1h30m`, `2h` or `45m` into seconds.
+pub fn parse_time_spent(time: &str) -> Result<i64, PiqoError> {
+    let error = || {
+        PiqoError::InvalidInput(format!(
+            "invalid time {}, expected e.g. 1h30m, 2h or 45m",
+            time
+        ))
+    };
+    let mut seconds = 0;
+    let mut amount = String::new();
+    for c in time.trim().chars() {
+        match c {
+            '0'..='9' => amount.push(c),
+            'h' | 'm' if !amount.is_empty() => {
+                let unit = if c == 'h' { 60 * 60 } else { 60 };
+                seconds = amount
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|amount| amount.checked_mul(unit))
+                    .and_then(|part| part.checked_add(seconds))
+                    .ok_or_else(error)?;
+                amount.clear();
+            }
+            _ => return Err(error()),
+        }
+    }
+    if !amount.is_empty() || seconds == 0 {
+        return Err(error());
+    }
+    Ok(seconds)
+}
+
+/// Formats seconds like `1h30m`, leaving out zero parts.
+pub fn format_time_spent(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+/// Ids of history events, drawn from a much larger range than the 4 digit ids as a
+/// project collects many of them and they are created concurrently on different branches.
+pub fn get_unused_event_id(current_ids: Vec<u64>) -> u64 {
+    let mut rng = rand::rng();
+    loop {
+        let id = rng.random_range(1..u64::from(u32::MAX));
+        if !current_ids.contains(&id) {
+            return id;
+        }
+    }
+}
+
+pub fn truncate(s: &str, max_len: usize) -> String {
     if s.chars().count() > max_len - 1 {
-        format!("{}…", &s[..max_len - 1])
+        format!("{}…", s.chars().take(max_len - 1).collect::<String>())
     } else {
         s.to_owned()
     }
 }
-pub(crate) fn center_align(text: &str, width: usize) -> String {
+pub fn center_align(text: &str, width: usize) -> String {
     format!("{: ^width$}", text, width = width)
 }
-pub(crate) fn left_align(text: &str, width: usize) -> String {
+pub fn left_align(text: &str, width: usize) -> String {
     format!("{:<width$}", text, width = width)
 }
 
-pub(crate) fn truncate_then_center_align(text: &str, width: usize) -> String {
+pub fn truncate_then_center_align(text: &str, width: usize) -> String {
     let truncated = self::truncate(text, width);
     self::center_align(&truncated, width)
 }
 
-pub(crate) fn print_line_left(text: &str, width: usize) {
+pub fn print_line_left(text: &str, width: usize) {
     println!("{}", self::left_align(text, width));
 }
-pub(crate) fn print_line_centered(text: &str, width: usize) {
+pub fn print_line_centered(text: &str, width: usize) {
     println!("{}", self::center_align(text, width));
 }
-pub(crate) fn print_divider(width: usize) {
+pub fn print_divider(width: usize) {
     println!("{}", "-".repeat(width));
 }
-pub(crate) fn format_description(s: &str, max_len: usize) -> Vec<String> {
+pub fn format_description(s: &str, max_len: usize) -> Vec<String> {
     let mut segments = Vec::new();
     s.lines().for_each(|f| {
         segments.extend(self::to_segments(f, max_len));
@@ -139,3 +196,61 @@ pub fn display_due_date_time(seconds_till: i64) -> (String, Style) {
 
     (format!("{}{}", time_val, letter), color)
 }
+
+/// Parses `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` or RFC 3339 into a unix timestamp.
+/// Plain dates are due at the end of the day, same as the `Midnight` prompt option.
+pub fn parse_due_date(due: &str) -> Result<i64, PiqoError> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(due) {
+        return Ok(date_time.timestamp());
+    }
+    let naive = NaiveDateTime::parse_from_str(due, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(due, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(due, "%Y-%m-%d").map(|d| d.and_hms_opt(23, 59, 0).unwrap())
+        })
+        .map_err(|_| PiqoError::InvalidInput(format!("invalid due date: {}", due)))?;
+
+    match Local.from_local_datetime(&naive).earliest() {
+        Some(date_time) => Ok(date_time.timestamp()),
+        None => Err(PiqoError::InvalidInput(format!(
+            "invalid local time: {}",
+            due
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hours_and_minutes() {
+        assert_eq!(parse_time_spent("2h").unwrap(), 2 * 60 * 60);
+        assert_eq!(parse_time_spent("45m").unwrap(), 45 * 60);
+        assert_eq!(parse_time_spent(" 1h30m ").unwrap(), 90 * 60);
+        assert_eq!(parse_time_spent("30m1h").unwrap(), 90 * 60);
+    }
+
+    #[test]
+    fn rejects_malformed_time() {
+        for time in [
+            "", "0h", "0m", "1", "h", "1h30", "1.5h", "-1h", "+1h", "1d", "1 h",
+        ] {
+            assert!(parse_time_spent(time).is_err(), "{}", time);
+        }
+    }
+
+    #[test]
+    fn rejects_time_that_overflows() {
+        assert!(parse_time_spent("99999999999999999h").is_err());
+        assert!(parse_time_spent("99999999999999999999m").is_err());
+        assert!(parse_time_spent("2562047788015215h2562047788015215h").is_err());
+    }
+
+    #[test]
+    fn formats_time_spent() {
+        assert_eq!(format_time_spent(45 * 60), "45m");
+        assert_eq!(format_time_spent(2 * 60 * 60), "2h");
+        assert_eq!(format_time_spent(90 * 60), "1h30m");
+    }
+}